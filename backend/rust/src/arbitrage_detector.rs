@@ -1,14 +1,17 @@
 use serde::{Serialize, Deserialize};
-use crate::profit_calculator::{calculate_profit_metrics};
+use schemars::JsonSchema;
+use crate::profit_calculator::{calculate_profit_metrics, calculate_futures_costs, calculate_cash_trade_costs};
+use crate::trend_tracker::{Trend, TrendStats};
+use crate::opportunity::{leg, new_opportunity, provenance, DataSource, Opportunity, Side, StrategyKind, ToOpportunities};
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct RiskMetrics {
     pub suggested_stop_loss: f64,
     pub suggested_position_size: f64,
     pub risk_reward_ratio: f64,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ArbitrageResult {
     pub opportunity: bool,
     pub symbol: String,
@@ -22,8 +25,10 @@ pub struct ArbitrageResult {
     pub gross_profit: f64,
     pub margin_required: f64,
     pub roi_percentage: f64,
-    pub spread_trend: String,
+    pub spread_trend: Trend,
+    pub trend_stats: Option<TrendStats>,
     pub risk_metrics: RiskMetrics,
+    pub expiry: String,
     pub last_update: String,
 }
 
@@ -88,15 +93,42 @@ pub fn detect_cash_futures_arbitrage( symbol: &str, spot_price: f64, futures_pri
         gross_profit: profit_metrics.gross_profit,
         margin_required: profit_metrics.margin_required,
         roi_percentage: profit_metrics.roi_percentage,
-        spread_trend: "stable".to_string(),
+        spread_trend: Trend::Stable,
+        trend_stats: None,
         risk_metrics,
+        expiry: String::new(),
         last_update: chrono::Local::now().format("%H:%M:%S").to_string(),
     }
 }
 
+impl ToOpportunities for ArbitrageResult {
+    fn to_opportunities(&self, source: DataSource) -> Vec<Opportunity> {
+        if !self.opportunity {
+            return Vec::new();
+        }
+
+        let (spot_side, futures_side) = if self.spread > 0.0 { (Side::Buy, Side::Sell) } else { (Side::Sell, Side::Buy) };
+        let legs = vec![
+            leg(&self.symbol, spot_side, self.lot_size, self.spot_price),
+            leg(format!("{} FUT", self.symbol), futures_side, self.lot_size, self.futures_price),
+        ];
+
+        let contract_value = self.futures_price * self.lot_size as f64;
+        let spot_value = self.spot_price * self.lot_size as f64;
+        let costs = calculate_futures_costs(contract_value)
+            + calculate_cash_trade_costs(spot_value, spot_value);
+
+        let mut opportunity = new_opportunity(StrategyKind::CashFutures, &self.symbol, legs, self.gross_profit, costs, provenance("arbitrage_detector", source, &["futures_price"]));
+        opportunity.expiry = (!self.expiry.is_empty()).then(|| self.expiry.clone());
+        opportunity.details = self.details.clone();
+        vec![opportunity]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::opportunity::YAHOO_DELAYED;
     
     #[test]
     fn test_arbitrage_detection() {
@@ -111,5 +143,17 @@ mod tests {
         let result = detect_cash_futures_arbitrage("TCS", 3950.0, 3952.0, 0.5);
         assert!(!result.opportunity);
         assert_eq!(result.action, "HOLD");
+        assert!(result.to_opportunities(YAHOO_DELAYED).is_empty());
+    }
+
+    #[test]
+    fn test_opportunity_envelope() {
+        let opportunities = detect_cash_futures_arbitrage("RELIANCE", 2850.0, 2865.0, 0.5).to_opportunities(YAHOO_DELAYED);
+        assert_eq!(opportunities.len(), 1);
+        let opp = &opportunities[0];
+        assert_eq!(opp.kind, StrategyKind::CashFutures);
+        assert_eq!(opp.legs[0].side, Side::Buy);
+        assert_eq!(opp.legs[1].side, Side::Sell);
+        assert!(opp.costs > 0.0 && opp.net_edge < opp.edge);
     }
 }
\ No newline at end of file
@@ -0,0 +1,468 @@
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use schemars::JsonSchema;
+use log::{info, warn};
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+pub const AUTH_CONFIG_ENV: &str = "AUTH_CONFIG";
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const DEFAULT_REQUESTS_PER_MINUTE: u32 = 120;
+pub const SESSION_TTL_SECS: i64 = 15 * 60;
+
+// Trading covers everything read-only does, plus the endpoints that change engine state
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    ReadOnly,
+    Trading,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ApiKey {
+    pub name: String,
+    pub key: String,
+    pub scope: Scope,
+    pub requests_per_minute: Option<u32>,
+}
+
+// Loaded from the JSON file named by AUTH_CONFIG. With no API keys and no JWT secret,
+// authentication is off, which is only meant for a server bound to 127.0.0.1
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub api_keys: Vec<ApiKey>,
+    // HS256 secret; tokens carry `sub`, `scope` and `exp` claims
+    pub jwt_secret: Option<String>,
+    // for API keys without their own limit and for every JWT subject
+    pub requests_per_minute: u32,
+    pub cors_allowed_origins: Vec<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            api_keys: Vec::new(),
+            jwt_secret: None,
+            requests_per_minute: DEFAULT_REQUESTS_PER_MINUTE,
+            cors_allowed_origins: vec!["http://localhost:3000".to_string(), "http://127.0.0.1:3000".to_string()],
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn enabled(&self) -> bool {
+        !self.api_keys.is_empty() || self.jwt_secret.is_some()
+    }
+
+    fn check(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut names = HashSet::new();
+        let mut keys = HashSet::new();
+        for api_key in &self.api_keys {
+            if api_key.key.len() < 16 {
+                return Err(format!("API key '{}' must be at least 16 characters", api_key.name).into());
+            }
+            if !names.insert(&api_key.name) || !keys.insert(&api_key.key) {
+                return Err(format!("API key '{}' is configured twice", api_key.name).into());
+            }
+            if api_key.requests_per_minute == Some(0) {
+                return Err(format!("API key '{}' has a zero rate limit", api_key.name).into());
+            }
+        }
+        if self.jwt_secret.as_ref().is_some_and(|s| s.len() < 32) {
+            return Err("jwt_secret must be at least 32 characters".into());
+        }
+        if self.requests_per_minute == 0 {
+            return Err("requests_per_minute must be positive".into());
+        }
+        // warp panics on a malformed origin, so catch it here
+        for origin in &self.cors_allowed_origins {
+            let well_formed = origin == "*"
+                || origin
+                    .split_once("://")
+                    .is_some_and(|(scheme, host)| matches!(scheme, "http" | "https") && !host.is_empty() && !host.contains('/'));
+            if !well_formed {
+                return Err(format!("CORS origin '{}' must look like https://host[:port]", origin).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn auth_config_from_env() -> Result<AuthConfig, Box<dyn std::error::Error + Send + Sync>> {
+    let config: AuthConfig = match std::env::var(AUTH_CONFIG_ENV) {
+        Ok(path) => serde_json::from_str(&std::fs::read_to_string(&path)?)?,
+        Err(_) => AuthConfig::default(),
+    };
+    config.check()?;
+    if config.enabled() {
+        info!("Authentication enabled: {} API keys, JWT {}", config.api_keys.len(), if config.jwt_secret.is_some() { "accepted" } else { "disabled" });
+    } else {
+        warn!("Authentication disabled; set {} before exposing the server beyond 127.0.0.1", AUTH_CONFIG_ENV);
+    }
+    Ok(config)
+}
+
+// The scope the gate asks of every request, or None for endpoints that stay public.
+// Routes that need more add require() to their own filter chain, so the check only
+// applies once warp has matched the route rather than to a spelling of its path
+pub fn required_scope(path: &str) -> Option<Scope> {
+    match path {
+        "/api/openapi.json" | "/healthz" | "/readyz" => None,
+        _ => Some(Scope::ReadOnly),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    scope: Scope,
+    exp: i64,
+}
+
+// A read-only JWT for one browser session, so the API key behind the dashboard can
+// stay on its server
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Session {
+    // bearer token for the session; null when authentication is off
+    pub token: Option<String>,
+    pub scope: Scope,
+    // unix seconds
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    pub name: String,
+    pub scope: Scope,
+    pub requests_per_minute: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthError {
+    MissingToken,
+    InvalidToken,
+    InsufficientScope(Scope),
+    RateLimited(Duration),
+    ApiKeyRequired,
+    SessionsUnavailable,
+}
+
+impl warp::reject::Reject for AuthError {}
+
+impl AuthError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AuthError::MissingToken | AuthError::InvalidToken => StatusCode::UNAUTHORIZED,
+            AuthError::InsufficientScope(_) | AuthError::ApiKeyRequired => StatusCode::FORBIDDEN,
+            AuthError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            AuthError::SessionsUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            AuthError::MissingToken => format!("missing credentials: send an API key in {}, a bearer token, or access_token in the query", API_KEY_HEADER),
+            AuthError::InvalidToken => "invalid or expired credentials".to_string(),
+            AuthError::InsufficientScope(scope) => format!("requires the {} scope", serde_json::to_value(scope).unwrap().as_str().unwrap()),
+            AuthError::RateLimited(retry_after) => format!("rate limit exceeded, retry in {}s", retry_after.as_secs().max(1)),
+            AuthError::ApiKeyRequired => "session tokens are only issued to API keys".to_string(),
+            AuthError::SessionsUnavailable => "session tokens need jwt_secret in the auth config".to_string(),
+        }
+    }
+}
+
+// Refills continuously at requests_per_minute / 60 per second, up to one minute's worth
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn take(&mut self, requests_per_minute: u32, now: Instant) -> Result<(), Duration> {
+        let capacity = requests_per_minute as f64;
+        let per_sec = capacity / 60.0;
+        self.tokens = (self.tokens + now.duration_since(self.updated).as_secs_f64() * per_sec).min(capacity);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / per_sec))
+        }
+    }
+}
+
+// compares every byte so the time taken does not reveal how much of a key matched
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub struct Authenticator {
+    config: AuthConfig,
+    jwt_key: Option<DecodingKey>,
+    session_key: Option<EncodingKey>,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl Authenticator {
+    pub fn new(config: AuthConfig) -> Self {
+        let jwt_key = config.jwt_secret.as_ref().map(|secret| DecodingKey::from_secret(secret.as_bytes()));
+        let session_key = config.jwt_secret.as_ref().map(|secret| EncodingKey::from_secret(secret.as_bytes()));
+        Authenticator { config, jwt_key, session_key, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    fn api_key(&self, token: &str) -> Option<&ApiKey> {
+        self.config.api_keys.iter().find(|k| constant_time_eq(&k.key, token))
+    }
+
+    // An API key is matched first; anything else is tried as a JWT
+    pub fn authenticate(&self, token: &str) -> Result<Principal, AuthError> {
+        if let Some(api_key) = self.api_key(token) {
+            return Ok(Principal {
+                name: format!("key:{}", api_key.name),
+                scope: api_key.scope,
+                requests_per_minute: api_key.requests_per_minute.unwrap_or(self.config.requests_per_minute),
+            });
+        }
+        let jwt_key = self.jwt_key.as_ref().ok_or(AuthError::InvalidToken)?;
+        let claims = decode::<Claims>(token, jwt_key, &Validation::new(Algorithm::HS256))
+            .map_err(|_| AuthError::InvalidToken)?
+            .claims;
+        Ok(Principal {
+            name: format!("jwt:{}", claims.sub),
+            scope: claims.scope,
+            requests_per_minute: self.config.requests_per_minute,
+        })
+    }
+
+    // Checks the scope without touching the rate limit, for route-level checks on
+    // requests the gate has already counted
+    pub fn permit(&self, token: Option<&str>, required: Scope) -> Result<(), AuthError> {
+        if !self.config.enabled() {
+            return Ok(());
+        }
+        self.principal(token, required).map(|_| ())
+    }
+
+    pub fn authorize(&self, token: Option<&str>, required: Option<Scope>, now: Instant) -> Result<(), AuthError> {
+        let Some(required) = required.filter(|_| self.config.enabled()) else {
+            return Ok(());
+        };
+        let principal = self.principal(token, required)?;
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(principal.name.clone())
+            .or_insert_with(|| TokenBucket { tokens: principal.requests_per_minute as f64, updated: now });
+        bucket.take(principal.requests_per_minute, now).map_err(|retry_after| {
+            warn!("Rate limited {}", principal.name);
+            AuthError::RateLimited(retry_after)
+        })
+    }
+
+    // Only API keys may ask, so a session token cannot renew itself past its expiry.
+    // The session is rate-limited as the JWT subject named after the key, apart from the key
+    pub fn issue_session(&self, token: Option<&str>, now: i64) -> Result<Session, AuthError> {
+        if !self.config.enabled() {
+            return Ok(Session { token: None, scope: Scope::ReadOnly, expires_at: None });
+        }
+        let token = token.ok_or(AuthError::MissingToken)?;
+        self.authenticate(token)?;
+        let api_key = self.api_key(token).ok_or(AuthError::ApiKeyRequired)?;
+        let session_key = self.session_key.as_ref().ok_or(AuthError::SessionsUnavailable)?;
+        let claims = Claims { sub: api_key.name.clone(), scope: Scope::ReadOnly, exp: now + SESSION_TTL_SECS };
+        let session = encode(&Header::default(), &claims, session_key).map_err(|_| AuthError::SessionsUnavailable)?;
+        info!("Issued a session token for {}", api_key.name);
+        Ok(Session { token: Some(session), scope: claims.scope, expires_at: Some(claims.exp) })
+    }
+
+    fn principal(&self, token: Option<&str>, required: Scope) -> Result<Principal, AuthError> {
+        let principal = self.authenticate(token.ok_or(AuthError::MissingToken)?)?;
+        if principal.scope < required {
+            return Err(AuthError::InsufficientScope(required));
+        }
+        Ok(principal)
+    }
+}
+
+// access_token is for browsers, whose WebSocket and EventSource cannot set headers
+#[derive(Deserialize)]
+struct TokenQuery {
+    access_token: Option<String>,
+}
+
+fn bearer(authorization: Option<String>) -> Option<String> {
+    authorization.and_then(|value| value.strip_prefix("Bearer ").map(|token| token.trim().to_string()))
+}
+
+// The API key header, then a bearer token, then access_token in the query
+pub fn credentials() -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>(API_KEY_HEADER)
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::query::<TokenQuery>())
+        .map(|api_key: Option<String>, authorization: Option<String>, query: TokenQuery| {
+            api_key.or_else(|| bearer(authorization)).or(query.access_token)
+        })
+}
+
+// Checks every request against required_scope before any route runs, so each request
+// is authenticated and counted against its key's rate limit exactly once
+pub fn gate(auth: Arc<Authenticator>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path::full()
+        .and(credentials())
+        .and_then(move |path: warp::path::FullPath, token: Option<String>| {
+            let auth = auth.clone();
+            async move {
+                auth.authorize(token.as_deref(), required_scope(path.as_str()), Instant::now())
+                    .map_err(warp::reject::custom)
+            }
+        })
+        .untuple_one()
+}
+
+// Route-level scope check; goes after the route's path and method filters
+pub fn require(auth: Arc<Authenticator>, scope: Scope) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    credentials()
+        .and_then(move |token: Option<String>| {
+            let auth = auth.clone();
+            async move { auth.permit(token.as_deref(), scope).map_err(warp::reject::custom) }
+        })
+        .untuple_one()
+}
+
+// Turns auth rejections into JSON errors; anything else falls through to warp's defaults
+pub async fn handle_rejection(rejection: Rejection) -> Result<warp::reply::Response, Rejection> {
+    let Some(error) = rejection.find::<AuthError>() else {
+        return Err(rejection);
+    };
+    let mut response = warp::reply::with_status(warp::reply::json(&serde_json::json!({ "error": error.message() })), error.status()).into_response();
+    let headers = response.headers_mut();
+    match error {
+        AuthError::MissingToken | AuthError::InvalidToken => {
+            headers.insert("www-authenticate", "Bearer".parse().unwrap());
+        }
+        AuthError::RateLimited(retry_after) => {
+            headers.insert("retry-after", retry_after.as_secs().max(1).into());
+        }
+        AuthError::InsufficientScope(_) | AuthError::ApiKeyRequired | AuthError::SessionsUnavailable => {}
+    }
+    Ok(response)
+}
+
+pub fn cors(config: &AuthConfig) -> warp::filters::cors::Builder {
+    let cors = warp::cors()
+        .allow_headers(vec!["content-type", "last-event-id", "authorization", API_KEY_HEADER])
+        .allow_methods(vec!["GET", "POST", "DELETE"]);
+    if config.cors_allowed_origins.iter().any(|o| o == "*") {
+        warn!("CORS allows any origin");
+        cors.allow_any_origin()
+    } else {
+        cors.allow_origins(config.cors_allowed_origins.iter().map(String::as_str))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn authenticator() -> Authenticator {
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({
+            "api_keys": [
+                { "name": "dashboard", "key": "read-key-0123456789", "scope": "read_only", "requests_per_minute": 2 },
+                { "name": "desk", "key": "trade-key-0123456789", "scope": "trading" }
+            ],
+            "jwt_secret": SECRET,
+        }))
+        .unwrap();
+        config.check().unwrap();
+        Authenticator::new(config)
+    }
+
+    fn jwt(scope: &str, exp_offset_secs: i64) -> String {
+        let claims = serde_json::json!({ "sub": "alice", "scope": scope, "exp": chrono::Utc::now().timestamp() + exp_offset_secs });
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(SECRET.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_scopes_and_credentials() {
+        let auth = authenticator();
+        let now = Instant::now();
+        let trading = Some(Scope::Trading);
+        assert_eq!(required_scope("/api/watchlist"), Some(Scope::ReadOnly));
+        assert_eq!(required_scope("/api/options/pcp/"), Some(Scope::ReadOnly));
+        assert_eq!(required_scope("/api/openapi.json"), None);
+        assert_eq!(required_scope("/healthz"), None);
+        assert_eq!(required_scope("/api/status"), Some(Scope::ReadOnly));
+        assert_eq!(auth.permit(Some("read-key-0123456789"), Scope::Trading), Err(AuthError::InsufficientScope(Scope::Trading)));
+        assert_eq!(auth.permit(Some("trade-key-0123456789"), Scope::Trading), Ok(()));
+
+        assert_eq!(auth.authorize(None, Some(Scope::ReadOnly), now), Err(AuthError::MissingToken));
+        assert_eq!(auth.authorize(Some("guess-0123456789"), Some(Scope::ReadOnly), now), Err(AuthError::InvalidToken));
+        assert_eq!(auth.authorize(None, None, now), Ok(()));
+        assert_eq!(auth.authorize(Some("read-key-0123456789"), trading, now), Err(AuthError::InsufficientScope(Scope::Trading)));
+        assert_eq!(auth.authorize(Some("trade-key-0123456789"), trading, now), Ok(()));
+
+        assert_eq!(auth.authorize(Some(&jwt("trading", 600)), trading, now), Ok(()));
+        assert_eq!(auth.authorize(Some(&jwt("read_only", 600)), trading, now), Err(AuthError::InsufficientScope(Scope::Trading)));
+        assert_eq!(auth.authorize(Some(&jwt("trading", -600)), trading, now), Err(AuthError::InvalidToken));
+
+        let open = Authenticator::new(AuthConfig::default());
+        assert_eq!(open.authorize(None, trading, now), Ok(()));
+        assert_eq!(open.permit(None, Scope::Trading), Ok(()));
+    }
+
+    #[test]
+    fn test_sessions_are_short_lived_and_read_only() {
+        let auth = authenticator();
+        let now = chrono::Utc::now().timestamp();
+        let session = auth.issue_session(Some("trade-key-0123456789"), now).unwrap();
+        assert_eq!(session.scope, Scope::ReadOnly);
+        assert_eq!(session.expires_at, Some(now + SESSION_TTL_SECS));
+        let token = session.token.unwrap();
+        assert_eq!(auth.authenticate(&token).unwrap().name, "jwt:desk");
+        assert_eq!(auth.permit(Some(&token), Scope::Trading), Err(AuthError::InsufficientScope(Scope::Trading)));
+        assert_eq!(auth.issue_session(Some(&token), now).unwrap_err(), AuthError::ApiKeyRequired);
+        assert_eq!(auth.issue_session(None, now).unwrap_err(), AuthError::MissingToken);
+
+        let expired = auth.issue_session(Some("read-key-0123456789"), now - 2 * SESSION_TTL_SECS).unwrap().token.unwrap();
+        assert_eq!(auth.authenticate(&expired), Err(AuthError::InvalidToken));
+
+        let keys_only: AuthConfig = serde_json::from_value(serde_json::json!({
+            "api_keys": [{ "name": "desk", "key": "trade-key-0123456789", "scope": "trading" }],
+        }))
+        .unwrap();
+        let keys_only = Authenticator::new(keys_only);
+        assert_eq!(keys_only.issue_session(Some("trade-key-0123456789"), now).unwrap_err(), AuthError::SessionsUnavailable);
+        assert!(Authenticator::new(AuthConfig::default()).issue_session(None, now).unwrap().token.is_none());
+    }
+
+    #[test]
+    fn test_rate_limit_is_per_key_and_refills() {
+        let auth = authenticator();
+        let now = Instant::now();
+        let read = Some(Scope::ReadOnly);
+        assert!(auth.authorize(Some("read-key-0123456789"), read, now).is_ok());
+        assert!(auth.authorize(Some("read-key-0123456789"), read, now).is_ok());
+        let Err(AuthError::RateLimited(retry_after)) = auth.authorize(Some("read-key-0123456789"), read, now) else {
+            panic!("expected the third request to be limited");
+        };
+        assert!(retry_after <= Duration::from_secs(30));
+        assert!(auth.authorize(Some("trade-key-0123456789"), read, now).is_ok());
+        assert!(auth.authorize(Some("read-key-0123456789"), read, now + Duration::from_secs(30)).is_ok());
+    }
+
+    #[test]
+    fn test_config_rejects_weak_or_malformed_settings() {
+        let parse = |value: serde_json::Value| serde_json::from_value::<AuthConfig>(value).unwrap().check();
+        assert!(parse(serde_json::json!({ "api_keys": [{ "name": "a", "key": "short", "scope": "trading" }] })).is_err());
+        assert!(parse(serde_json::json!({ "jwt_secret": "too-short" })).is_err());
+        assert!(parse(serde_json::json!({ "cors_allowed_origins": ["localhost:3000"] })).is_err());
+        assert!(parse(serde_json::json!({ "cors_allowed_origins": ["https://desk.example.com", "http://localhost:3000"] })).is_ok());
+        assert!(!AuthConfig::default().enabled());
+    }
+}
@@ -1,94 +1,796 @@
 use serde::{Serialize, Deserialize};
-use rand::Rng;
+use schemars::JsonSchema;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::validation::{FieldError, FieldErrors, Validate};
 
-#[derive(Serialize, Deserialize)]
+const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+pub const MAX_SIMULATIONS: usize = 100_000;
+pub const MAX_DAYS: usize = 2520;
+// every simulated day is kept for the percentile bands, so bound the total
+pub const MAX_SIMULATED_DAYS: usize = 10_000_000;
+pub const MAX_SIZING_COMPARISONS: usize = 8;
+
+fn default_ruin_floor_pct() -> f64 {
+    50.0
+}
+
+fn default_confidence_level() -> f64 {
+    0.95
+}
+
+fn default_histogram_bins() -> usize {
+    20
+}
+
+fn default_block_size() -> usize {
+    5
+}
+
+fn default_max_leverage() -> f64 {
+    5.0
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulationMode {
+    #[default]
+    Parametric,
+    Bootstrap,
+}
+
+// How much capital each day's trade return is applied to
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, JsonSchema)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum PositionSizing {
+    // a constant share of current equity, compounding
+    FixedFractional { fraction: f64 },
+    // a constant notional regardless of equity, no compounding
+    FixedLots { lots: u32, lot_value: f64 },
+    Kelly,
+    FractionalKelly { multiplier: f64 },
+    // scale exposure so the trailing realised volatility matches the target
+    VolatilityTarget { target_annual_vol: f64, lookback: usize },
+}
+
+impl Default for PositionSizing {
+    fn default() -> Self {
+        PositionSizing::FixedFractional { fraction: 1.0 }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct BacktestParams {
     pub initial_capital: f64,
     pub num_simulations: usize,
     pub days: usize,
-    pub win_rate: f64,
-    pub avg_win_pct: f64,
-    pub avg_loss_pct: f64,
+    #[serde(default)]
+    pub mode: SimulationMode,
+    // parametric mode draws wins and losses from these and requires all three
+    #[serde(default)]
+    pub win_rate: Option<f64>,
+    #[serde(default)]
+    pub avg_win_pct: Option<f64>,
+    #[serde(default)]
+    pub avg_loss_pct: Option<f64>,
+    // bootstrap mode resamples these per-trade returns (fractions, e.g. 0.004) in
+    // consecutive blocks; left empty, the route fills them from the stored history
+    #[serde(default)]
+    pub trade_returns: Vec<f64>,
+    #[serde(default = "default_block_size")]
+    pub block_size: usize,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // a path is ruined once equity touches this share of initial capital
+    #[serde(default = "default_ruin_floor_pct")]
+    pub ruin_floor_pct: f64,
+    #[serde(default = "default_confidence_level")]
+    pub confidence_level: f64,
+    #[serde(default = "default_histogram_bins")]
+    pub histogram_bins: usize,
+    #[serde(default)]
+    pub position_sizing: PositionSizing,
+    // extra policies run on the same random draws for a side-by-side comparison
+    #[serde(default)]
+    pub compare_sizing: Vec<PositionSizing>,
+    // exposure as a multiple of equity is capped here for every policy
+    #[serde(default = "default_max_leverage")]
+    pub max_leverage: f64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct SimulationResult {
     pub equity_curve: Vec<f64>,
     pub final_equity: f64,
     pub max_drawdown: f64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PercentileBand {
+    pub day: usize,
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct HistogramBin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DistributionSummary {
+    pub mean: f64,
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SizingComparison {
+    pub sizing: PositionSizing,
+    pub average_final_equity: f64,
+    pub median_final_equity: f64,
+    pub average_max_drawdown: f64,
+    pub ruin_probability: f64,
+    pub median_sharpe: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct BacktestResponse {
+    pub mode: SimulationMode,
+    pub source_sample_size: usize,
     pub average_final_equity: f64,
     pub best_case_equity: f64,
     pub worst_case_equity: f64,
     pub average_max_drawdown: f64,
     pub sample_simulations: Vec<SimulationResult>,
+    pub seed: u64, // pass back in the request to reproduce this run exactly
+    pub percentile_bands: Vec<PercentileBand>,
+    pub final_equity_histogram: Vec<HistogramBin>,
+    // VaR and ES are losses on initial capital over the whole horizon, in rupees
+    pub value_at_risk: f64,
+    pub expected_shortfall: f64,
+    pub ruin_probability: f64,
+    pub sharpe_distribution: DistributionSummary,
+    pub sortino_distribution: DistributionSummary,
+    pub position_sizing: PositionSizing,
+    // Kelly fraction estimated from the return model, before any leverage cap
+    pub kelly_fraction: f64,
+    // the primary policy first, then each entry of compare_sizing
+    pub sizing_comparison: Vec<SizingComparison>,
 }
 
-pub fn run_monte_carlo(params: BacktestParams) -> BacktestResponse {
-    let mut rng = rand::rng();
-    let mut all_simulations = Vec::new();
-    
-    let mut total_final_equity = 0.0;
-    let mut total_max_drawdown = 0.0;
-    let mut best_case = params.initial_capital;
-    let mut worst_case = f64::MAX;
-
-    for _ in 0..params.num_simulations {
-        let mut equity = params.initial_capital;
-        let mut peak = equity;
-        let mut max_drawdown = 0.0;
-        let mut equity_curve = Vec::with_capacity(params.days);
-        
-        equity_curve.push(equity);
+// Shared between a running simulation and whoever started it, so the caller
+// can poll progress and stop the run early
+#[derive(Default)]
+pub struct RunControl {
+    cancelled: AtomicBool,
+    completed: AtomicUsize,
+}
+
+impl RunControl {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed.load(Ordering::Relaxed)
+    }
+}
+
+struct PathOutcome {
+    equity_curve: Vec<f64>,
+    max_drawdown: f64,
+    ruined: bool,
+    sharpe: f64,
+    sortino: f64,
+}
+
+// linear interpolation between closest ranks; `sorted` must be ascending
+fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (pct / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+fn sort_values(values: &mut [f64]) {
+    values.sort_by(|a, b| a.total_cmp(b));
+}
+
+fn summarize(mut values: Vec<f64>) -> DistributionSummary {
+    sort_values(&mut values);
+    let mean = if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 };
+    DistributionSummary {
+        mean,
+        p5: percentile(&values, 5.0),
+        p25: percentile(&values, 25.0),
+        p50: percentile(&values, 50.0),
+        p75: percentile(&values, 75.0),
+        p95: percentile(&values, 95.0),
+    }
+}
+
+fn histogram(sorted: &[f64], bins: usize) -> Vec<HistogramBin> {
+    let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+        return Vec::new();
+    };
+    let bins = bins.max(1);
+    let width = (max - min) / bins as f64;
 
-        for _ in 1..=params.days {
-            let is_win = rng.random::<f64>() < params.win_rate;
-            let pnl_pct = if is_win {
-                params.avg_win_pct * (0.5 + rng.random::<f64>())
+    let mut histogram: Vec<HistogramBin> = (0..bins)
+        .map(|i| HistogramBin {
+            lower: min + width * i as f64,
+            upper: min + width * (i + 1) as f64,
+            count: 0,
+        })
+        .collect();
+    for &value in sorted {
+        let index = if width > 0.0 { (((value - min) / width) as usize).min(bins - 1) } else { 0 };
+        histogram[index].count += 1;
+    }
+    histogram
+}
+
+// annualised Sharpe and Sortino of the daily returns along one path
+fn risk_adjusted_returns(daily_returns: &[f64]) -> (f64, f64) {
+    if daily_returns.len() < 2 {
+        return (0.0, 0.0);
+    }
+    let n = daily_returns.len() as f64;
+    let mean = daily_returns.iter().sum::<f64>() / n;
+    let std_dev = (daily_returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+    let downside_dev = (daily_returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / n).sqrt();
+    let annualise = TRADING_DAYS_PER_YEAR.sqrt();
+
+    let sharpe = if std_dev > 0.0 { mean / std_dev * annualise } else { 0.0 };
+    let sortino = if downside_dev > 0.0 { mean / downside_dev * annualise } else { 0.0 };
+    (sharpe, sortino)
+}
+
+// (win rate, average win, average loss); the loss is a negative return, e.g. -0.008.
+// Validation has already required all three in parametric mode
+fn parametric_model(params: &BacktestParams) -> (f64, f64, f64) {
+    (
+        params.win_rate.unwrap_or_default(),
+        params.avg_win_pct.unwrap_or_default(),
+        params.avg_loss_pct.unwrap_or_default(),
+    )
+}
+
+fn parametric_returns(rng: &mut impl Rng, params: &BacktestParams) -> Vec<f64> {
+    let (win_rate, win, loss) = parametric_model(params);
+    (0..params.days)
+        .map(|_| {
+            let is_win = rng.random::<f64>() < win_rate;
+            if is_win {
+                win * (0.5 + rng.random::<f64>())
             } else {
-                params.avg_loss_pct * (0.5 + rng.random::<f64>())
-            };
+                loss * (0.5 + rng.random::<f64>())
+            }
+        })
+        .collect()
+}
 
-            equity = equity * (1.0 + pnl_pct);
-            equity_curve.push(equity);
+// circular block bootstrap: whole runs of consecutive trades are copied so
+// streaks and other serial correlation in the history carry into the paths
+fn bootstrap_returns(rng: &mut impl Rng, history: &[f64], block_size: usize, days: usize) -> Vec<f64> {
+    let block_size = block_size.clamp(1, history.len());
+    let mut returns = Vec::with_capacity(days);
+    while returns.len() < days {
+        let start = rng.random_range(0..history.len());
+        let remaining = days - returns.len();
+        returns.extend((0..block_size.min(remaining)).map(|i| history[(start + i) % history.len()]));
+    }
+    returns
+}
 
-            if equity > peak {
-                peak = equity;
+// Full Kelly leverage for the return model: the binary win/loss formula for
+// parametric draws, mean over variance for an empirical sample
+pub fn kelly_fraction(params: &BacktestParams) -> f64 {
+    match params.mode {
+        SimulationMode::Parametric => {
+            let (win_rate, win, loss) = parametric_model(params);
+            if win <= 0.0 || loss >= 0.0 {
+                return 0.0;
+            }
+            win_rate / -loss - (1.0 - win_rate) / win
+        }
+        SimulationMode::Bootstrap => {
+            let n = params.trade_returns.len() as f64;
+            if n < 2.0 {
+                return 0.0;
             }
+            let mean = params.trade_returns.iter().sum::<f64>() / n;
+            let variance = params.trade_returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            if variance > 0.0 { mean / variance } else { 0.0 }
+        }
+    }
+}
 
-            let drawdown = (peak - equity) / peak;
-            if drawdown > max_drawdown {
-                max_drawdown = drawdown;
+// rupee exposure for the next trade given the equity and the returns seen so far
+fn exposure(sizing: &PositionSizing, equity: f64, past_returns: &[f64], kelly: f64, max_leverage: f64) -> f64 {
+    let leverage = match *sizing {
+        PositionSizing::FixedFractional { fraction } => fraction,
+        PositionSizing::FixedLots { lots, lot_value } => return (lots as f64 * lot_value).min(equity * max_leverage),
+        PositionSizing::Kelly => kelly,
+        PositionSizing::FractionalKelly { multiplier } => kelly * multiplier,
+        PositionSizing::VolatilityTarget { target_annual_vol, lookback } => {
+            let window = &past_returns[past_returns.len().saturating_sub(lookback)..];
+            if lookback < 2 || window.len() < lookback {
+                1.0
+            } else {
+                let mean = window.iter().sum::<f64>() / window.len() as f64;
+                let realised = (window.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (window.len() - 1) as f64).sqrt();
+                if realised > 0.0 { target_annual_vol / TRADING_DAYS_PER_YEAR.sqrt() / realised } else { max_leverage }
             }
         }
+    };
+    equity * leverage.clamp(0.0, max_leverage)
+}
 
-        if equity > best_case {
-            best_case = equity;
+fn simulate_path(rng: &mut impl Rng, params: &BacktestParams, sizing: &PositionSizing, kelly: f64) -> PathOutcome {
+    let ruin_floor = params.initial_capital * params.ruin_floor_pct / 100.0;
+    let mut equity = params.initial_capital;
+    let mut peak = equity;
+    let mut max_drawdown = 0.0;
+    let mut ruined = false;
+    let mut equity_curve = Vec::with_capacity(params.days + 1);
+    let mut daily_returns = Vec::with_capacity(params.days);
+
+    equity_curve.push(equity);
+
+    let draws = match params.mode {
+        SimulationMode::Parametric => parametric_returns(rng, params),
+        SimulationMode::Bootstrap => bootstrap_returns(rng, &params.trade_returns, params.block_size, params.days),
+    };
+
+    for (day, pnl_pct) in draws.iter().enumerate() {
+        let pnl = exposure(sizing, equity, &draws[..day], kelly, params.max_leverage) * pnl_pct;
+        let equity_return = if equity > 0.0 { pnl / equity } else { 0.0 };
+        // a wiped-out account stays at zero
+        equity = (equity + pnl).max(0.0);
+        equity_curve.push(equity);
+        daily_returns.push(equity_return);
+
+        if equity > peak {
+            peak = equity;
         }
-        if equity < worst_case {
-            worst_case = equity;
+
+        let drawdown = (peak - equity) / peak;
+        if drawdown > max_drawdown {
+            max_drawdown = drawdown;
+        }
+        if equity <= ruin_floor {
+            ruined = true;
         }
+    }
+
+    let (sharpe, sortino) = risk_adjusted_returns(&daily_returns);
+    PathOutcome {
+        equity_curve,
+        max_drawdown,
+        ruined,
+        sharpe,
+        sortino,
+    }
+}
+
+fn simulate_paths(path_seeds: &[u64], params: &BacktestParams, sizing: &PositionSizing, kelly: f64, control: &RunControl) -> Option<Vec<PathOutcome>> {
+    path_seeds
+        .par_iter()
+        .map(|path_seed| {
+            if control.is_cancelled() {
+                return None;
+            }
+            let path = simulate_path(&mut StdRng::seed_from_u64(*path_seed), params, sizing, kelly);
+            control.completed.fetch_add(1, Ordering::Relaxed);
+            Some(path)
+        })
+        .collect()
+}
+
+fn compare_sizing(sizing: PositionSizing, paths: &[PathOutcome]) -> SizingComparison {
+    let n = paths.len() as f64;
+    let mut final_equities: Vec<f64> = paths.iter().map(|p| *p.equity_curve.last().unwrap()).collect();
+    sort_values(&mut final_equities);
+    let mut sharpes: Vec<f64> = paths.iter().map(|p| p.sharpe).collect();
+    sort_values(&mut sharpes);
+
+    SizingComparison {
+        sizing,
+        average_final_equity: final_equities.iter().sum::<f64>() / n,
+        median_final_equity: percentile(&final_equities, 50.0),
+        average_max_drawdown: paths.iter().map(|p| p.max_drawdown).sum::<f64>() / n * 100.0,
+        ruin_probability: paths.iter().filter(|p| p.ruined).count() as f64 / n * 100.0,
+        median_sharpe: percentile(&sharpes, 50.0),
+    }
+}
+
+// simulated paths a run will produce, for progress reporting
+pub fn total_paths(params: &BacktestParams) -> usize {
+    params.num_simulations.saturating_mul(1 + params.compare_sizing.len())
+}
 
-        total_final_equity += equity;
-        total_max_drawdown += max_drawdown;
+fn check_sizing(errors: &mut FieldErrors, field: &str, sizing: &PositionSizing) {
+    match *sizing {
+        PositionSizing::FixedFractional { fraction } => errors.check(fraction.is_finite() && fraction >= 0.0, &format!("{}.fraction", field), "must not be negative"),
+        PositionSizing::FixedLots { lots, lot_value } => {
+            errors.check(lots > 0, &format!("{}.lots", field), "must be at least 1");
+            errors.positive(&format!("{}.lot_value", field), lot_value);
+        }
+        PositionSizing::Kelly => {}
+        PositionSizing::FractionalKelly { multiplier } => errors.positive(&format!("{}.multiplier", field), multiplier),
+        PositionSizing::VolatilityTarget { target_annual_vol, lookback } => {
+            errors.positive(&format!("{}.target_annual_vol", field), target_annual_vol);
+            errors.check(lookback >= 2, &format!("{}.lookback", field), "must be at least 2");
+        }
+    }
+}
 
-        if all_simulations.len() < 5 {
-            all_simulations.push(SimulationResult {
-                equity_curve,
-                final_equity: equity,
-                max_drawdown: max_drawdown * 100.0,
-            });
+fn field_errors(params: &BacktestParams) -> FieldErrors {
+    let mut errors = FieldErrors::default();
+    errors.positive("initial_capital", params.initial_capital);
+    errors.check(params.num_simulations > 0, "num_simulations", "must be at least 1");
+    errors.check(params.num_simulations <= MAX_SIMULATIONS, "num_simulations", format!("is limited to {}", MAX_SIMULATIONS));
+    errors.check(params.days <= MAX_DAYS, "days", format!("is limited to {}", MAX_DAYS));
+    // the budget covers every path run, one set per sizing policy compared
+    errors.check(
+        total_paths(params).saturating_mul(params.days) <= MAX_SIMULATED_DAYS,
+        "num_simulations",
+        format!("times days, times the number of sizing policies, is limited to {}", MAX_SIMULATED_DAYS),
+    );
+    if params.mode == SimulationMode::Parametric {
+        if let Some(win_rate) = errors.required("win_rate", params.win_rate) {
+            errors.in_range("win_rate", win_rate, 0.0, 1.0);
         }
+        if let Some(avg_win_pct) = errors.required("avg_win_pct", params.avg_win_pct) {
+            errors.in_range("avg_win_pct", avg_win_pct, 0.0, 1.0);
+        }
+        // a loss is a negative return; a positive value would be drawn as a gain
+        if let Some(avg_loss_pct) = errors.required("avg_loss_pct", params.avg_loss_pct) {
+            errors.in_range("avg_loss_pct", avg_loss_pct, -1.0, 0.0);
+        }
+    }
+    errors.check(params.trade_returns.iter().all(|r| r.is_finite() && *r > -1.0), "trade_returns", "must be finite returns above -1.0");
+    errors.check(params.block_size > 0, "block_size", "must be at least 1");
+    errors.in_range("ruin_floor_pct", params.ruin_floor_pct, 0.0, 100.0);
+    errors.check(params.confidence_level > 0.0 && params.confidence_level < 1.0, "confidence_level", "must be strictly between 0 and 1");
+    errors.check(params.histogram_bins > 0 && params.histogram_bins <= 1000, "histogram_bins", "must be between 1 and 1000");
+    errors.positive("max_leverage", params.max_leverage);
+    check_sizing(&mut errors, "position_sizing", &params.position_sizing);
+    errors.check(params.compare_sizing.len() <= MAX_SIZING_COMPARISONS, "compare_sizing", format!("is limited to {} policies", MAX_SIZING_COMPARISONS));
+    for (i, sizing) in params.compare_sizing.iter().enumerate() {
+        check_sizing(&mut errors, &format!("compare_sizing[{}]", i), sizing);
     }
+    errors
+}
+
+impl Validate for BacktestParams {
+    type Output = BacktestParams;
 
-    BacktestResponse {
-        average_final_equity: total_final_equity / (params.num_simulations as f64),
+    fn validate(self) -> Result<BacktestParams, Vec<FieldError>> {
+        field_errors(&self).into_result()?;
+        Ok(self)
+    }
+}
+
+pub fn validate_params(params: &BacktestParams) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    field_errors(params).into_result().map_err(|errors| {
+        errors.iter().map(|e| format!("{} {}", e.field, e.message)).collect::<Vec<_>>().join("; ").into()
+    })
+}
+
+pub fn run_monte_carlo(params: BacktestParams, control: &RunControl) -> Result<BacktestResponse, Box<dyn std::error::Error + Send + Sync>> {
+    validate_params(&params)?;
+    if params.mode == SimulationMode::Bootstrap && params.trade_returns.is_empty() {
+        return Err("bootstrap mode needs at least one trade return".into());
+    }
+
+    // unseeded runs still draw a seed so the response can be replayed
+    let seed = params.seed.unwrap_or_else(|| rand::rng().random());
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // one seed per path keeps the result independent of how rayon schedules the work
+    let path_seeds: Vec<u64> = (0..params.num_simulations).map(|_| rng.random()).collect();
+    let kelly = kelly_fraction(&params);
+    let paths = simulate_paths(&path_seeds, &params, &params.position_sizing, kelly, control).ok_or("simulation cancelled")?;
+
+    // reusing the path seeds gives every policy the same trade returns
+    let mut sizing_comparison = vec![compare_sizing(params.position_sizing, &paths)];
+    for sizing in &params.compare_sizing {
+        let policy_paths = simulate_paths(&path_seeds, &params, sizing, kelly, control).ok_or("simulation cancelled")?;
+        sizing_comparison.push(compare_sizing(*sizing, &policy_paths));
+    }
+
+    let mut final_equities: Vec<f64> = paths.iter().map(|p| *p.equity_curve.last().unwrap()).collect();
+    let total_max_drawdown: f64 = paths.iter().map(|p| p.max_drawdown).sum();
+    let best_case = final_equities.iter().cloned().fold(params.initial_capital, f64::max);
+    let worst_case = final_equities.iter().cloned().fold(f64::MAX, f64::min);
+    let average_final_equity = final_equities.iter().sum::<f64>() / (params.num_simulations as f64);
+
+    let percentile_bands = (0..=params.days)
+        .into_par_iter()
+        .map(|day| {
+            let mut column: Vec<f64> = paths.iter().map(|p| p.equity_curve[day]).collect();
+            sort_values(&mut column);
+            PercentileBand {
+                day,
+                p5: percentile(&column, 5.0),
+                p25: percentile(&column, 25.0),
+                p50: percentile(&column, 50.0),
+                p75: percentile(&column, 75.0),
+                p95: percentile(&column, 95.0),
+            }
+        })
+        .collect();
+
+    sort_values(&mut final_equities);
+    let tail_pct = (1.0 - params.confidence_level.clamp(0.0, 1.0)) * 100.0;
+    let var_equity = percentile(&final_equities, tail_pct);
+    let tail: Vec<f64> = final_equities.iter().cloned().filter(|e| *e <= var_equity).collect();
+    let expected_shortfall = if tail.is_empty() {
+        0.0
+    } else {
+        params.initial_capital - tail.iter().sum::<f64>() / tail.len() as f64
+    };
+
+    let ruined = paths.iter().filter(|p| p.ruined).count();
+
+    Ok(BacktestResponse {
+        mode: params.mode,
+        source_sample_size: params.trade_returns.len(),
+        average_final_equity,
         best_case_equity: best_case,
         worst_case_equity: worst_case,
         average_max_drawdown: (total_max_drawdown / (params.num_simulations as f64)) * 100.0,
-        sample_simulations: all_simulations,
+        sharpe_distribution: summarize(paths.iter().map(|p| p.sharpe).collect()),
+        sortino_distribution: summarize(paths.iter().map(|p| p.sortino).collect()),
+        ruin_probability: ruined as f64 / params.num_simulations as f64 * 100.0,
+        sample_simulations: paths
+            .into_iter()
+            .take(5)
+            .map(|p| SimulationResult {
+                final_equity: *p.equity_curve.last().unwrap(),
+                max_drawdown: p.max_drawdown * 100.0,
+                equity_curve: p.equity_curve,
+            })
+            .collect(),
+        seed,
+        percentile_bands,
+        final_equity_histogram: histogram(&final_equities, params.histogram_bins),
+        value_at_risk: params.initial_capital - var_equity,
+        expected_shortfall,
+        position_sizing: params.position_sizing,
+        kelly_fraction: kelly,
+        sizing_comparison,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(seed: Option<u64>) -> BacktestParams {
+        BacktestParams {
+            initial_capital: 100_000.0,
+            num_simulations: 50,
+            days: 30,
+            mode: SimulationMode::Parametric,
+            win_rate: Some(0.6),
+            avg_win_pct: Some(0.01),
+            avg_loss_pct: Some(-0.008),
+            trade_returns: Vec::new(),
+            block_size: default_block_size(),
+            seed,
+            ruin_floor_pct: default_ruin_floor_pct(),
+            confidence_level: default_confidence_level(),
+            histogram_bins: default_histogram_bins(),
+            position_sizing: PositionSizing::default(),
+            compare_sizing: Vec::new(),
+            max_leverage: default_max_leverage(),
+        }
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_run() {
+        let first = run_monte_carlo(params(Some(42)), &RunControl::default()).unwrap();
+        let second = run_monte_carlo(params(Some(42)), &RunControl::default()).unwrap();
+        assert_eq!(first.seed, 42);
+        assert_eq!(first.average_final_equity, second.average_final_equity);
+        assert_eq!(first.sample_simulations[0].equity_curve, second.sample_simulations[0].equity_curve);
+
+        let other = run_monte_carlo(params(Some(43)), &RunControl::default()).unwrap();
+        assert_ne!(first.average_final_equity, other.average_final_equity);
+    }
+
+    #[test]
+    fn test_unseeded_run_echoes_replayable_seed() {
+        let first = run_monte_carlo(params(None), &RunControl::default()).unwrap();
+        let replay = run_monte_carlo(params(Some(first.seed)), &RunControl::default()).unwrap();
+        assert_eq!(first.average_final_equity, replay.average_final_equity);
+        assert_eq!(first.worst_case_equity, replay.worst_case_equity);
+    }
+
+    #[test]
+    fn test_risk_statistics_are_consistent() {
+        let result = run_monte_carlo(BacktestParams { num_simulations: 500, ..params(Some(7)) }, &RunControl::default()).unwrap();
+
+        assert_eq!(result.percentile_bands.len(), 31);
+        let last = result.percentile_bands.last().unwrap();
+        assert!(last.p5 <= last.p25 && last.p25 <= last.p50 && last.p50 <= last.p75 && last.p75 <= last.p95);
+        assert_eq!(result.percentile_bands[0].p5, 100_000.0);
+
+        assert_eq!(result.final_equity_histogram.len(), 20);
+        assert_eq!(result.final_equity_histogram.iter().map(|b| b.count).sum::<usize>(), 500);
+        assert!(result.expected_shortfall >= result.value_at_risk);
+        assert_eq!(result.ruin_probability, 0.0);
+        assert!(result.sortino_distribution.p50 >= result.sharpe_distribution.p50);
+    }
+
+    #[test]
+    fn test_positive_loss_is_rejected() {
+        let errors = BacktestParams { avg_loss_pct: Some(0.008), ..params(None) }.validate().err().unwrap();
+        assert_eq!(errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(), vec!["avg_loss_pct"]);
+        assert!(run_monte_carlo(BacktestParams { avg_loss_pct: Some(0.008), ..params(Some(4)) }, &RunControl::default()).is_err());
+        assert!(params(None).validate().is_ok());
+    }
+
+    #[test]
+    fn test_ruin_probability_with_losing_strategy() {
+        let losing = BacktestParams {
+            win_rate: Some(0.2),
+            avg_loss_pct: Some(-0.05),
+            ruin_floor_pct: 80.0,
+            ..params(Some(3))
+        };
+        let result = run_monte_carlo(losing, &RunControl::default()).unwrap();
+        assert!(result.ruin_probability > 90.0);
+        assert!(result.value_at_risk > 20_000.0);
+    }
+
+    #[test]
+    fn test_bootstrap_resamples_history_in_blocks() {
+        let history = vec![0.01, 0.02, -0.01, 0.03, -0.02];
+        let mut rng = StdRng::seed_from_u64(5);
+        let draws = bootstrap_returns(&mut rng, &history, 3, 10);
+        assert_eq!(draws.len(), 10);
+        assert!(draws.iter().all(|r| history.contains(r)));
+
+        // each block continues the history in order, wrapping at the end
+        let start = history.iter().position(|r| *r == draws[0]).unwrap();
+        assert_eq!(draws[1], history[(start + 1) % history.len()]);
+        assert_eq!(draws[2], history[(start + 2) % history.len()]);
+
+        let bootstrap = BacktestParams {
+            mode: SimulationMode::Bootstrap,
+            trade_returns: history,
+            ..params(Some(9))
+        };
+        let result = run_monte_carlo(bootstrap, &RunControl::default()).unwrap();
+        assert_eq!(result.mode, SimulationMode::Bootstrap);
+        assert_eq!(result.source_sample_size, 5);
+        assert_eq!(result.sample_simulations[0].equity_curve.len(), 31);
+    }
+
+    #[test]
+    fn test_bootstrap_without_returns_is_rejected() {
+        let result = run_monte_carlo(BacktestParams { mode: SimulationMode::Bootstrap, ..params(Some(1)) }, &RunControl::default());
+        assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_request_limits() {
+        assert!(run_monte_carlo(BacktestParams { num_simulations: 0, ..params(Some(1)) }, &RunControl::default()).is_err());
+        assert!(run_monte_carlo(BacktestParams { days: MAX_DAYS + 1, ..params(Some(1)) }, &RunControl::default()).is_err());
+        assert!(validate_params(&BacktestParams { num_simulations: MAX_SIMULATIONS, days: MAX_DAYS, ..params(None) }).is_err());
+        let within_budget = BacktestParams { num_simulations: MAX_SIMULATED_DAYS / 2 / MAX_DAYS, days: MAX_DAYS, ..params(None) };
+        assert!(validate_params(&within_budget).is_ok());
+        let compared = BacktestParams { compare_sizing: vec![PositionSizing::default(); 2], ..within_budget };
+        assert_eq!(compared.validate().err().unwrap()[0].field, "num_simulations");
+
+        let invalid = BacktestParams {
+            initial_capital: 0.0,
+            win_rate: Some(1.5),
+            confidence_level: 1.0,
+            compare_sizing: vec![PositionSizing::VolatilityTarget { target_annual_vol: 0.2, lookback: 1 }],
+            ..params(None)
+        };
+        let fields: Vec<String> = invalid.validate().err().unwrap().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["initial_capital", "win_rate", "confidence_level", "compare_sizing[0].lookback"]);
+
+        let missing = BacktestParams { avg_win_pct: None, avg_loss_pct: None, ..params(None) };
+        let errors = missing.validate().err().unwrap();
+        assert_eq!(errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(), vec!["avg_win_pct", "avg_loss_pct"]);
+        assert_eq!(errors[0].message, "is required");
+        let bootstrap = BacktestParams { mode: SimulationMode::Bootstrap, win_rate: None, trade_returns: vec![0.01], ..params(None) };
+        assert!(bootstrap.validate().is_ok());
+
+        let control = RunControl::default();
+        let result = run_monte_carlo(BacktestParams { days: 0, ..params(Some(1)) }, &control).unwrap();
+        assert_eq!(result.average_final_equity, 100_000.0);
+        assert_eq!(control.completed(), 50);
+    }
+
+    #[test]
+    fn test_cancelled_run_stops() {
+        let control = RunControl::default();
+        control.cancel();
+        assert!(run_monte_carlo(params(Some(1)), &control).is_err());
+        assert_eq!(control.completed(), 0);
+    }
+
+    #[test]
+    fn test_sizing_policies_share_draws() {
+        let half = PositionSizing::FixedFractional { fraction: 0.5 };
+        let comparison = BacktestParams {
+            days: 1,
+            compare_sizing: vec![half, PositionSizing::FixedLots { lots: 2, lot_value: 10_000.0 }],
+            ..params(Some(11))
+        };
+        let control = RunControl::default();
+        let total = total_paths(&comparison);
+        let result = run_monte_carlo(comparison, &control).unwrap();
+        assert_eq!(control.completed(), total);
+
+        // with a single day every policy is a linear scaling of the same draw
+        let full = &result.sizing_comparison[0];
+        let halved = &result.sizing_comparison[1];
+        let lots = &result.sizing_comparison[2];
+        assert!(((full.average_final_equity - 100_000.0) * 0.5 - (halved.average_final_equity - 100_000.0)).abs() < 1e-6);
+        assert!(((full.average_final_equity - 100_000.0) * 0.2 - (lots.average_final_equity - 100_000.0)).abs() < 1e-6);
+        assert_eq!(result.sizing_comparison[1].sizing, half);
+    }
+
+    #[test]
+    fn test_kelly_fraction_and_leverage_cap() {
+        // 0.6 / 0.008 - 0.4 / 0.01 = 35x, far above any margin allowance
+        let base = params(Some(2));
+        assert!((kelly_fraction(&base) - 35.0).abs() < 1e-9);
+        assert_eq!(exposure(&PositionSizing::Kelly, 100_000.0, &[], 35.0, 5.0), 500_000.0);
+        assert_eq!(exposure(&PositionSizing::FractionalKelly { multiplier: 0.1 }, 100_000.0, &[], 35.0, 5.0), 350_000.0);
+
+        let losing = BacktestParams { win_rate: Some(0.3), ..params(Some(2)) };
+        assert!(kelly_fraction(&losing) < 0.0);
+        assert_eq!(exposure(&PositionSizing::Kelly, 100_000.0, &[], kelly_fraction(&losing), 5.0), 0.0);
+    }
+
+    #[test]
+    fn test_volatility_target_scales_exposure() {
+        let sizing = PositionSizing::VolatilityTarget { target_annual_vol: 0.16, lookback: 4 };
+        // not enough history yet: fully invested
+        assert_eq!(exposure(&sizing, 100_000.0, &[0.01], 0.0, 5.0), 100_000.0);
+
+        let calm = [0.001, -0.001, 0.001, -0.001];
+        let wild = [0.02, -0.02, 0.02, -0.02];
+        let calm_exposure = exposure(&sizing, 100_000.0, &calm, 0.0, 5.0);
+        let wild_exposure = exposure(&sizing, 100_000.0, &wild, 0.0, 5.0);
+        assert_eq!(calm_exposure, 500_000.0);
+        assert!(wild_exposure < 100_000.0);
+    }
+
+    #[test]
+    fn test_percentile_interpolates() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(percentile(&values, 50.0), 3.0);
+        assert_eq!(percentile(&values, 25.0), 2.0);
+        assert!((percentile(&values, 10.0) - 1.4).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,236 @@
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use crate::nse_data_api::{Exchange, StockPrice};
+use crate::profit_calculator::calculate_cash_trade_costs;
+use crate::opportunity::{leg, new_opportunity, provenance, DataSource, Opportunity, Side, StrategyKind, ToOpportunities};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CrossExchangeConfig {
+    pub threshold_percentage: f64, // net of costs
+    pub trade_value: f64,
+    pub max_quote_age_secs: i64,
+    pub max_leg_skew_secs: i64,
+    pub max_latency_ms: u64,
+}
+
+impl Default for CrossExchangeConfig {
+    fn default() -> Self {
+        // Yahoo quotes are delayed up to 15 minutes, so the age limit is loose;
+        // the legs still have to be close to each other to be comparable
+        CrossExchangeConfig {
+            threshold_percentage: 0.05,
+            trade_value: 100_000.0,
+            max_quote_age_secs: 20 * 60,
+            max_leg_skew_secs: 60,
+            max_latency_ms: 3000,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct LegQuality {
+    pub exchange: Exchange,
+    pub price: f64,
+    pub quote_age_secs: Option<i64>,
+    pub latency_ms: u64,
+    pub stale: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CrossExchangeResult {
+    pub kind: String,
+    pub opportunity: bool,
+    pub symbol: String,
+    pub nse_price: f64,
+    pub bse_price: f64,
+    pub spread: f64,
+    pub spread_percentage: f64,
+    pub action: String,
+    pub quantity: u32,
+    pub gross_profit: f64,
+    pub estimated_costs: f64,
+    pub net_profit: f64,
+    pub net_spread_percentage: f64,
+    pub leg_skew_secs: Option<i64>,
+    pub nse_leg: LegQuality,
+    pub bse_leg: LegQuality,
+    pub details: String,
+    pub last_update: String,
+}
+
+fn leg_quality(exchange: Exchange, quote: &StockPrice, now_unix: i64, config: &CrossExchangeConfig) -> LegQuality {
+    let quote_age_secs = quote.exchange_time.map(|t| (now_unix - t).max(0));
+    let stale = match quote_age_secs {
+        Some(age) => age > config.max_quote_age_secs,
+        None => true,
+    } || quote.latency_ms > config.max_latency_ms;
+
+    LegQuality {
+        exchange,
+        price: quote.ltp,
+        quote_age_secs,
+        latency_ms: quote.latency_ms,
+        stale,
+    }
+}
+
+pub fn detect_cross_exchange_arbitrage(
+    symbol: &str,
+    nse_quote: &StockPrice,
+    bse_quote: &StockPrice,
+    now_unix: i64,
+    config: &CrossExchangeConfig,
+) -> CrossExchangeResult {
+    let nse_leg = leg_quality(Exchange::Nse, nse_quote, now_unix, config);
+    let bse_leg = leg_quality(Exchange::Bse, bse_quote, now_unix, config);
+
+    let spread = bse_quote.ltp - nse_quote.ltp;
+    let spread_percentage = spread / nse_quote.ltp * 100.0;
+
+    let (buy_exchange, sell_exchange, buy_price, sell_price) = if spread >= 0.0 {
+        (Exchange::Nse, Exchange::Bse, nse_quote.ltp, bse_quote.ltp)
+    } else {
+        (Exchange::Bse, Exchange::Nse, bse_quote.ltp, nse_quote.ltp)
+    };
+
+    let quantity = (config.trade_value / buy_price).floor().max(1.0) as u32;
+    let gross_profit = (sell_price - buy_price) * quantity as f64;
+    let estimated_costs = calculate_cash_trade_costs(buy_price * quantity as f64, sell_price * quantity as f64);
+    let net_profit = gross_profit - estimated_costs;
+    let net_spread_percentage = net_profit / (buy_price * quantity as f64) * 100.0;
+
+    let leg_skew_secs = match (nse_quote.exchange_time, bse_quote.exchange_time) {
+        (Some(nse_time), Some(bse_time)) => Some((nse_time - bse_time).abs()),
+        _ => None,
+    };
+    let legs_in_sync = leg_skew_secs.is_some_and(|skew| skew <= config.max_leg_skew_secs);
+    let tradable = !nse_leg.stale && !bse_leg.stale && legs_in_sync;
+
+    let opportunity = tradable && net_spread_percentage > config.threshold_percentage;
+
+    let action = if opportunity {
+        format!("BUY {}, SELL {}", buy_exchange.name(), sell_exchange.name())
+    } else {
+        "HOLD".to_string()
+    };
+
+    let details = if opportunity {
+        format!(
+            "NSE/BSE divergence for {}! NSE: ₹{:.2}, BSE: ₹{:.2}, Net: ₹{:.2} ({:.3}%) on {} shares",
+            symbol, nse_quote.ltp, bse_quote.ltp, net_profit, net_spread_percentage, quantity
+        )
+    } else if !tradable {
+        format!(
+            "Ignoring NSE/BSE spread for {}: stale or unsynchronised legs (NSE age {:?}s, BSE age {:?}s, skew {:?}s)",
+            symbol, nse_leg.quote_age_secs, bse_leg.quote_age_secs, leg_skew_secs
+        )
+    } else {
+        format!(
+            "No NSE/BSE arbitrage for {}. NSE: ₹{:.2}, BSE: ₹{:.2}, Spread: {:.3}% does not cover costs",
+            symbol, nse_quote.ltp, bse_quote.ltp, spread_percentage
+        )
+    };
+
+    CrossExchangeResult {
+        kind: "NSE_BSE".to_string(),
+        opportunity,
+        symbol: symbol.to_string(),
+        nse_price: nse_quote.ltp,
+        bse_price: bse_quote.ltp,
+        spread,
+        spread_percentage,
+        action,
+        quantity,
+        gross_profit,
+        estimated_costs,
+        net_profit,
+        net_spread_percentage,
+        leg_skew_secs,
+        nse_leg,
+        bse_leg,
+        details,
+        last_update: chrono::Local::now().format("%H:%M:%S").to_string(),
+    }
+}
+
+impl ToOpportunities for CrossExchangeResult {
+    fn to_opportunities(&self, source: DataSource) -> Vec<Opportunity> {
+        if !self.opportunity {
+            return Vec::new();
+        }
+
+        let (buy, sell) = if self.spread >= 0.0 { (&self.nse_leg, &self.bse_leg) } else { (&self.bse_leg, &self.nse_leg) };
+        let legs = vec![
+            leg(format!("{} {}", self.symbol, buy.exchange.name()), Side::Buy, self.quantity, buy.price),
+            leg(format!("{} {}", self.symbol, sell.exchange.name()), Side::Sell, self.quantity, sell.price),
+        ];
+
+        let mut opportunity = new_opportunity(StrategyKind::CrossExchange, &self.symbol, legs, self.gross_profit, self.estimated_costs, provenance("cross_exchange_arbitrage", source, &[]));
+        opportunity.details = self.details.clone();
+        vec![opportunity]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opportunity::YAHOO_DELAYED;
+
+    fn quote(ltp: f64, exchange_time: Option<i64>) -> StockPrice {
+        StockPrice {
+            symbol: "RELIANCE".to_string(),
+            ltp,
+            timestamp: String::new(),
+            exchange_time,
+            latency_ms: 150,
+        }
+    }
+
+    #[test]
+    fn test_divergence_beyond_costs() {
+        let now = 1_750_000_000;
+        let result = detect_cross_exchange_arbitrage(
+            "RELIANCE",
+            &quote(1300.0, Some(now - 30)),
+            &quote(1303.0, Some(now - 20)),
+            now,
+            &CrossExchangeConfig::default(),
+        );
+        assert!(result.opportunity);
+        assert_eq!(result.action, "BUY NSE, SELL BSE");
+        assert!(result.net_profit > 0.0 && result.net_profit < result.gross_profit);
+
+        let opportunities = result.to_opportunities(YAHOO_DELAYED);
+        assert_eq!(opportunities[0].legs[0].instrument, "RELIANCE NSE");
+        assert!((opportunities[0].net_edge - result.net_profit).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_small_spread_does_not_cover_costs() {
+        let now = 1_750_000_000;
+        let result = detect_cross_exchange_arbitrage(
+            "RELIANCE",
+            &quote(1300.0, Some(now - 30)),
+            &quote(1300.3, Some(now - 20)),
+            now,
+            &CrossExchangeConfig::default(),
+        );
+        assert!(!result.opportunity);
+        assert!(result.net_profit < 0.0);
+    }
+
+    #[test]
+    fn test_stale_leg_is_not_traded() {
+        let now = 1_750_000_000;
+        let result = detect_cross_exchange_arbitrage(
+            "RELIANCE",
+            &quote(1300.0, Some(now - 30)),
+            &quote(1310.0, Some(now - 3 * 3600)),
+            now,
+            &CrossExchangeConfig::default(),
+        );
+        assert!(result.bse_leg.stale);
+        assert!(!result.opportunity);
+        assert_eq!(result.action, "HOLD");
+    }
+}
@@ -1,11 +1,21 @@
 use std::fs::OpenOptions;
 use std::io::Write;
 use log::{info, error};
+use chrono::NaiveDateTime;
 use crate::arbitrage_detector::ArbitrageResult;
+use crate::opportunity::Opportunity;
 
-const LOG_FILE: &str = "arbitrage_log.csv";
+pub const LOG_FILE: &str = "arbitrage_log.csv";
+pub const OPPORTUNITY_LOG_FILE: &str = "opportunities_log.jsonl";
 
 pub fn initialize_csv_log() {
+    // keep earlier sessions: the log doubles as the price history the stat-arb module calibrates on
+    let has_history = std::fs::metadata(LOG_FILE).map(|m| m.len() > 0).unwrap_or(false);
+    if has_history {
+        info!("Appending to existing CSV log: {}", LOG_FILE);
+        return;
+    }
+
     match OpenOptions::new().create(true).write(true).truncate(true).open(LOG_FILE)
     {
         Ok(mut file) => {
@@ -22,8 +32,45 @@ pub fn initialize_csv_log() {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct LoggedObservation {
+    pub timestamp: NaiveDateTime,
+    pub symbol: String,
+    pub spot_price: f64,
+    pub futures_price: f64,
+}
+
+// Only the leading columns are read: the action column contains an unquoted comma
+pub fn load_logged_observations(path: &str) -> Result<Vec<LoggedObservation>, std::io::Error> {
+    let text = std::fs::read_to_string(path)?;
+    let mut observations = Vec::new();
+
+    for line in text.lines().skip(1) {
+        let fields: Vec<&str> = line.splitn(5, ',').collect();
+        if fields.len() < 4 {
+            continue;
+        }
+        let (Ok(timestamp), Ok(spot_price), Ok(futures_price)) = (
+            NaiveDateTime::parse_from_str(fields[0], "%Y-%m-%d %H:%M:%S"),
+            fields[2].parse::<f64>(),
+            fields[3].parse::<f64>(),
+        ) else {
+            continue;
+        };
+
+        observations.push(LoggedObservation {
+            timestamp,
+            symbol: fields[1].to_string(),
+            spot_price,
+            futures_price,
+        });
+    }
+
+    Ok(observations)
+}
+
 pub fn log_to_csv(result: &ArbitrageResult) {
-    match OpenOptions::new().create(true).write(true).append(true).open(LOG_FILE)
+    match OpenOptions::new().create(true).append(true).open(LOG_FILE)
     {
         Ok(mut file) => {
             let log_entry = format!(
@@ -53,6 +100,28 @@ pub fn log_to_csv(result: &ArbitrageResult) {
     }
 }
 
+// One JSON object per line, since legs don't flatten into CSV columns
+pub fn log_opportunity(opportunity: &Opportunity) {
+    match OpenOptions::new().create(true).append(true).open(OPPORTUNITY_LOG_FILE)
+    {
+        Ok(mut file) => {
+            let line = match serde_json::to_string(opportunity) {
+                Ok(line) => line,
+                Err(e) => {
+                    error!("Failed to serialize opportunity {}: {}", opportunity.id, e);
+                    return;
+                }
+            };
+            if let Err(e) = writeln!(file, "{}", line) {
+                error!("Failed to write opportunity log: {}", e);
+            }
+        }
+        Err(e) => {
+            error!("Failed to open opportunity log: {}", e);
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub fn export_summary_stats(results: &[ArbitrageResult]) -> Result<(), std::io::Error> {
     let summary_file = "arbitrage_summary.txt";
@@ -82,4 +151,20 @@ mod tests {
         initialize_csv_log();
         assert!(std::path::Path::new(LOG_FILE).exists());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_load_logged_observations() {
+        let path = std::env::temp_dir().join("arbitrage_log_load_test.csv");
+        std::fs::write(
+            &path,
+            "timestamp,symbol,spot_price,futures_price,spread,spread_percentage,action,opportunity,lot_size,gross_profit,margin_required,roi_percentage,trend\n\
+             2026-06-25 21:45:03,RELIANCE,1318.10,1308.06,-10.04,-0.76,SELL Spot, BUY Futures,true,250,2509.55,58862.78,4.26,stable\n\
+             garbage line\n",
+        ).unwrap();
+
+        let observations = load_logged_observations(path.to_str().unwrap()).unwrap();
+        assert_eq!(observations.len(), 1);
+        assert_eq!(observations[0].symbol, "RELIANCE");
+        assert_eq!(observations[0].futures_price, 1308.06);
+    }
+}
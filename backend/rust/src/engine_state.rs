@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use crate::arbitrage_detector::ArbitrageResult;
+use crate::feed_replay::{ReplayBuffer, REPLAY_CAPACITY};
+use crate::health::EngineHealth;
+use crate::market_data::MarketDataSource;
+use crate::trend_tracker::{SpreadHistory, TrendConfig};
+
+// State the fetch loop owns and the API reads: the quote source with its pooled
+// client, the spread history and its trend settings, recent feed messages, the
+// loop's own health, the watchlist, and the latest result per symbol
+pub struct EngineState {
+    pub market_data: MarketDataSource,
+    pub spread_history: SpreadHistory,
+    pub trend_config: TrendConfig,
+    pub feed_replay: ReplayBuffer,
+    pub health: EngineHealth,
+    watchlist: RwLock<Vec<String>>,
+    latest_results: RwLock<HashMap<String, ArbitrageResult>>,
+}
+
+// NSE tickers are upper case letters and digits, with the odd & or - (M&M, BAJAJ-AUTO)
+pub fn normalize_symbol(symbol: &str) -> Result<String, String> {
+    let symbol = symbol.trim().to_uppercase();
+    if symbol.is_empty() || symbol.len() > 20 {
+        return Err("symbol must be 1-20 characters".to_string());
+    }
+    if !symbol.chars().all(|c| c.is_ascii_alphanumeric() || c == '&' || c == '-') {
+        return Err(format!("invalid symbol '{}'", symbol));
+    }
+    Ok(symbol)
+}
+
+impl EngineState {
+    pub fn new(market_data: MarketDataSource, spread_history: SpreadHistory, trend_config: TrendConfig, watchlist: &[&str]) -> Self {
+        EngineState {
+            market_data,
+            spread_history,
+            trend_config,
+            feed_replay: ReplayBuffer::new(REPLAY_CAPACITY),
+            health: EngineHealth::new(std::time::Instant::now()),
+            watchlist: RwLock::new(watchlist.iter().map(|s| s.to_string()).collect()),
+            latest_results: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // the fetch loop reads this at the start of every cycle, so edits apply from the next one
+    pub fn watchlist(&self) -> Vec<String> {
+        self.watchlist.read().unwrap().clone()
+    }
+
+    pub fn is_watched(&self, symbol: &str) -> bool {
+        self.watchlist.read().unwrap().iter().any(|s| s == symbol)
+    }
+
+    // false if the symbol was already watched
+    pub fn watch(&self, symbol: &str) -> bool {
+        let mut watchlist = self.watchlist.write().unwrap();
+        if watchlist.iter().any(|s| s == symbol) {
+            return false;
+        }
+        watchlist.push(symbol.to_string());
+        true
+    }
+
+    // false if the symbol was not watched; its latest result goes with it so
+    // snapshots stop reporting it
+    pub fn unwatch(&self, symbol: &str) -> bool {
+        let mut watchlist = self.watchlist.write().unwrap();
+        let before = watchlist.len();
+        watchlist.retain(|s| s != symbol);
+        if watchlist.len() == before {
+            return false;
+        }
+        self.latest_results.write().unwrap().remove(symbol);
+        true
+    }
+
+    // only watched symbols are kept; the watchlist lock is held across the insert
+    // so an unwatch cannot slip in between the check and the write
+    pub fn record_result(&self, result: &ArbitrageResult) -> bool {
+        let watchlist = self.watchlist.read().unwrap();
+        if !watchlist.contains(&result.symbol) {
+            return false;
+        }
+        self.latest_results.write().unwrap().insert(result.symbol.clone(), result.clone());
+        true
+    }
+
+    pub fn latest_result(&self, symbol: &str) -> Option<ArbitrageResult> {
+        self.latest_results.read().unwrap().get(symbol).cloned()
+    }
+
+    pub fn latest_results(&self) -> Vec<ArbitrageResult> {
+        let mut results: Vec<ArbitrageResult> = self.latest_results.read().unwrap().values().cloned().collect();
+        results.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage_detector::detect_cash_futures_arbitrage;
+    use crate::trend_tracker::create_spread_tracker;
+
+    #[test]
+    fn test_normalize_symbol() {
+        assert_eq!(normalize_symbol(" tcs "), Ok("TCS".to_string()));
+        assert_eq!(normalize_symbol("m&m"), Ok("M&M".to_string()));
+        assert_eq!(normalize_symbol("BAJAJ-AUTO"), Ok("BAJAJ-AUTO".to_string()));
+        assert!(normalize_symbol("").is_err());
+        assert!(normalize_symbol("TCS.NS").is_err());
+        assert!(normalize_symbol("../etc").is_err());
+    }
+
+    #[test]
+    fn test_only_watched_results_are_recorded() {
+        let engine = EngineState::new(MarketDataSource::Yahoo(reqwest::Client::new()), create_spread_tracker(10), TrendConfig::default(), &["TCS"]);
+        let result = |symbol: &str| detect_cash_futures_arbitrage(symbol, 100.0, 101.0, 0.5);
+
+        assert!(engine.record_result(&result("TCS")));
+        assert!(!engine.record_result(&result("INFY")));
+        assert!(engine.latest_result("INFY").is_none());
+        assert_eq!(engine.latest_results().len(), 1);
+
+        assert!(engine.unwatch("TCS"));
+        assert!(!engine.record_result(&result("TCS")));
+        assert!(engine.latest_results().is_empty());
+    }
+
+    #[test]
+    fn test_unwatch_mid_cycle_drops_late_results() {
+        let engine = EngineState::new(MarketDataSource::Yahoo(reqwest::Client::new()), create_spread_tracker(10), TrendConfig::default(), &["TCS", "INFY"]);
+        let cycle = engine.watchlist();
+        assert!(engine.unwatch("INFY"));
+
+        let recorded: Vec<&String> = cycle
+            .iter()
+            .filter(|symbol| engine.is_watched(symbol))
+            .filter(|symbol| engine.record_result(&detect_cash_futures_arbitrage(symbol, 100.0, 101.0, 0.5)))
+            .collect();
+        assert_eq!(recorded, vec!["TCS"]);
+        assert!(engine.latest_result("INFY").is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use serde::Serialize;
+use schemars::JsonSchema;
+use crate::arbitrage_detector::ArbitrageResult;
+use crate::cross_exchange_arbitrage::CrossExchangeResult;
+use crate::index_arbitrage::IndexArbitrageResult;
+use crate::opportunity::{DataSource, Opportunity, ToOpportunities};
+use crate::stat_arbitrage::StatArbSignal;
+use crate::volatility_surface::VolatilitySurfaceScan;
+use crate::ws_protocol::Subscription;
+
+// Bumped whenever a payload changes shape incompatibly
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertLevel {
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+pub struct Alert {
+    pub level: AlertLevel,
+    pub symbol: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+pub struct StatusUpdate {
+    pub source: String,
+    pub cycle: u64,
+    pub symbols_fetched: usize,
+    pub symbols_failed: usize,
+    pub cycle_ms: u64,
+}
+
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+pub struct Heartbeat {
+    pub interval_secs: u64,
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+pub struct ErrorPayload {
+    pub message: String,
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+pub struct Ack {
+    pub action: String,
+    pub request_id: Option<String>,
+    pub subscription: Subscription,
+}
+
+// A detector's full output for the cycle, published whether or not it yields
+// opportunities, so subscribers also see clean scans and stat-arb exits
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+#[serde(tag = "detector", content = "result", rename_all = "snake_case")]
+pub enum DetectorResult {
+    VolatilitySurface(VolatilitySurfaceScan),
+    IndexBasket(IndexArbitrageResult),
+    CrossExchange(CrossExchangeResult),
+    StatisticalArbitrage(StatArbSignal),
+}
+
+impl ToOpportunities for DetectorResult {
+    fn to_opportunities(&self, source: DataSource) -> Vec<Opportunity> {
+        match self {
+            DetectorResult::VolatilitySurface(scan) => scan.to_opportunities(source),
+            DetectorResult::IndexBasket(result) => result.to_opportunities(source),
+            DetectorResult::CrossExchange(result) => result.to_opportunities(source),
+            DetectorResult::StatisticalArbitrage(signal) => signal.to_opportunities(source),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+pub struct Detection {
+    pub data_source: String,
+    pub simulated: bool,
+    #[serde(flatten)]
+    pub result: DetectorResult,
+}
+
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+pub struct Snapshot {
+    pub reason: String,
+    pub missed: u64,
+    pub results: Vec<ArbitrageResult>,
+}
+
+// Everything the server sends to feed clients. Quotes, opportunities, detections,
+// status and alerts go out on the broadcast channel; the rest are per-connection replies
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum FeedMessage {
+    Quote(Box<ArbitrageResult>),
+    Opportunity(Box<Opportunity>),
+    Detection(Box<Detection>),
+    Status(StatusUpdate),
+    Alert(Alert),
+    Heartbeat(Heartbeat),
+    Error(ErrorPayload),
+    Ack(Box<Ack>),
+    Snapshot(Snapshot),
+}
+
+impl FeedMessage {
+    // the envelope `type`, also used as the SSE event name
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FeedMessage::Quote(_) => "quote",
+            FeedMessage::Opportunity(_) => "opportunity",
+            FeedMessage::Detection(_) => "detection",
+            FeedMessage::Status(_) => "status",
+            FeedMessage::Alert(_) => "alert",
+            FeedMessage::Heartbeat(_) => "heartbeat",
+            FeedMessage::Error(_) => "error",
+            FeedMessage::Ack(_) => "ack",
+            FeedMessage::Snapshot(_) => "snapshot",
+        }
+    }
+
+    pub fn detection(result: DetectorResult, source: DataSource) -> Self {
+        FeedMessage::Detection(Box::new(Detection {
+            data_source: source.name.to_string(),
+            simulated: source.simulated,
+            result,
+        }))
+    }
+
+    pub fn error(message: &str, request_id: Option<String>) -> Self {
+        FeedMessage::Error(ErrorPayload { message: message.to_string(), request_id })
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Envelope<'a> {
+    pub version: u32,
+    pub seq: u64,
+    pub ts: i64,
+    #[serde(flatten)]
+    pub message: &'a FeedMessage,
+}
+
+impl<'a> Envelope<'a> {
+    pub fn new(seq: u64, message: &'a FeedMessage) -> Self {
+        Envelope {
+            version: PROTOCOL_VERSION,
+            seq,
+            ts: chrono::Utc::now().timestamp_millis(),
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_flattens_type_and_payload() {
+        let message = FeedMessage::Alert(Alert { level: AlertLevel::Warning, symbol: Some("TCS".to_string()), message: "fetch failed".to_string() });
+        let value = serde_json::to_value(Envelope::new(7, &message)).unwrap();
+        assert_eq!(value["type"], message.kind());
+        assert_eq!(value["version"], PROTOCOL_VERSION);
+        assert_eq!(value["seq"], 7);
+        assert!(value["ts"].as_i64().unwrap() > 0);
+        assert_eq!(value["payload"]["level"], "warning");
+        assert_eq!(value["payload"]["symbol"], "TCS");
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+use crate::feed::{Envelope, FeedMessage, PROTOCOL_VERSION};
+use crate::opportunity::{Opportunity, StrategyKind};
+
+pub const REPLAY_CAPACITY: usize = 1000;
+
+// A broadcast message with the feed-wide id it was published under; the id is the
+// SSE event id and the envelope seq, so clients can resume from it
+pub struct ReplayEvent {
+    pub id: u64,
+    pub ts: i64,
+    pub message: FeedMessage,
+}
+
+impl ReplayEvent {
+    pub fn envelope(&self) -> Envelope<'_> {
+        Envelope {
+            version: PROTOCOL_VERSION,
+            seq: self.id,
+            ts: self.ts,
+            message: &self.message,
+        }
+    }
+}
+
+// `missed` counts events after the requested id that have already been evicted
+pub struct Replay {
+    pub missed: u64,
+    pub events: Vec<Arc<ReplayEvent>>,
+}
+
+// The last REPLAY_CAPACITY feed messages, for SSE Last-Event-ID resume and long-poll
+pub struct ReplayBuffer {
+    capacity: usize,
+    events: Mutex<VecDeque<Arc<ReplayEvent>>>,
+    latest_id: watch::Sender<u64>,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> Self {
+        ReplayBuffer {
+            capacity: capacity.max(1),
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            latest_id: watch::Sender::new(0),
+        }
+    }
+
+    pub fn latest_id(&self) -> u64 {
+        *self.latest_id.borrow()
+    }
+
+    pub fn push(&self, message: FeedMessage) -> u64 {
+        let mut events = self.events.lock().unwrap();
+        let id = self.latest_id() + 1;
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(Arc::new(ReplayEvent { id, ts: chrono::Utc::now().timestamp_millis(), message }));
+        self.latest_id.send_replace(id);
+        id
+    }
+
+    pub fn since(&self, last_id: u64) -> Replay {
+        let events = self.events.lock().unwrap();
+        let missed = events.front().map_or(0, |oldest| oldest.id.saturating_sub(last_id.saturating_add(1)));
+        let start = events.partition_point(|e| e.id <= last_id);
+        Replay { missed, events: events.range(start..).cloned().collect() }
+    }
+
+    // An id ahead of anything published is from before a restart: resume from the
+    // start of this run instead of waiting for ids that may never come
+    pub fn resume_from(&self, last_id: u64) -> u64 {
+        if last_id > self.latest_id() { 0 } else { last_id }
+    }
+
+    // newest first, optionally narrowed to one symbol or strategy
+    pub fn recent_opportunities(&self, limit: usize, symbol: Option<&str>, kind: Option<StrategyKind>) -> Vec<Opportunity> {
+        let events = self.events.lock().unwrap();
+        events
+            .iter()
+            .rev()
+            .filter_map(|e| match &e.message {
+                FeedMessage::Opportunity(opportunity) => Some(opportunity),
+                _ => None,
+            })
+            .filter(|o| symbol.is_none_or(|s| o.symbol == s) && kind.is_none_or(|k| o.kind == k))
+            .take(limit)
+            .map(|o| o.as_ref().clone())
+            .collect()
+    }
+
+    // waits up to `timeout` for something newer than last_id; an empty replay means it timed out
+    pub async fn wait_since(&self, last_id: u64, timeout: Duration) -> Replay {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut changed = self.latest_id.subscribe();
+            let replay = self.since(last_id);
+            if replay.missed > 0 || !replay.events.is_empty() {
+                return replay;
+            }
+            if tokio::time::timeout_at(deadline, changed.changed()).await.is_err() {
+                return replay;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage_detector::detect_cash_futures_arbitrage;
+    use crate::feed::{Alert, AlertLevel};
+    use crate::opportunity::{ToOpportunities, YAHOO_DELAYED};
+
+    fn alert(message: &str) -> FeedMessage {
+        FeedMessage::Alert(Alert { level: AlertLevel::Warning, symbol: None, message: message.to_string() })
+    }
+
+    #[test]
+    fn test_resume_reports_evicted_events() {
+        let buffer = ReplayBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(alert(&i.to_string()));
+        }
+        assert_eq!(buffer.latest_id(), 5);
+
+        let replay = buffer.since(3);
+        assert_eq!(replay.missed, 0);
+        assert_eq!(replay.events.iter().map(|e| e.id).collect::<Vec<_>>(), vec![4, 5]);
+
+        let replay = buffer.since(0);
+        assert_eq!(replay.missed, 2);
+        assert_eq!(replay.events.len(), 3);
+        assert!(buffer.since(5).events.is_empty());
+        assert!(buffer.since(u64::MAX).events.is_empty());
+        assert_eq!(buffer.resume_from(5), 5);
+        assert_eq!(buffer.resume_from(9), 0);
+        assert_eq!(buffer.since(buffer.resume_from(u64::MAX)).events.len(), 3);
+
+        let envelope = serde_json::to_value(replay.events[0].envelope()).unwrap();
+        assert_eq!(envelope["seq"], 3);
+        assert_eq!(envelope["type"], "alert");
+    }
+
+    #[test]
+    fn test_recent_opportunities_newest_first() {
+        let buffer = ReplayBuffer::new(10);
+        for (symbol, spread) in [("TCS", 0.8), ("INFY", 0.9), ("TCS", -1.0)] {
+            let result = detect_cash_futures_arbitrage(symbol, 1000.0, 1000.0 * (1.0 + spread / 100.0), 0.5);
+            buffer.push(FeedMessage::Quote(Box::new(result.clone())));
+            for opportunity in result.to_opportunities(YAHOO_DELAYED) {
+                buffer.push(FeedMessage::Opportunity(Box::new(opportunity)));
+            }
+        }
+        let all = buffer.recent_opportunities(10, None, None);
+        assert_eq!(all.iter().map(|o| o.symbol.as_str()).collect::<Vec<_>>(), vec!["TCS", "INFY", "TCS"]);
+        assert_eq!(buffer.recent_opportunities(10, Some("TCS"), Some(StrategyKind::CashFutures)).len(), 2);
+        assert_eq!(buffer.recent_opportunities(1, None, None).len(), 1);
+        assert!(buffer.recent_opportunities(10, None, Some(StrategyKind::IndexBasket)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_wait_since_wakes_on_push_and_times_out() {
+        let buffer = Arc::new(ReplayBuffer::new(10));
+        assert!(buffer.wait_since(0, Duration::from_millis(20)).await.events.is_empty());
+
+        let publisher = buffer.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            publisher.push(alert("late"));
+        });
+        let replay = buffer.wait_since(0, Duration::from_secs(5)).await;
+        assert_eq!(replay.events.len(), 1);
+        assert_eq!(replay.events[0].id, 1);
+    }
+}
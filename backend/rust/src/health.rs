@@ -0,0 +1,371 @@
+use serde::Serialize;
+use schemars::JsonSchema;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+
+// A provider's circuit opens after this many consecutive failed fetches and stays
+// open for CIRCUIT_COOLDOWN_SECS before a single trial fetch is let through
+pub const CIRCUIT_FAILURE_THRESHOLD: u32 = 5;
+pub const CIRCUIT_COOLDOWN_SECS: u64 = 60;
+// /healthz fails when the fetch loop has made no progress for this long, /readyz when
+// no cycle has completed for this long
+pub const LIVENESS_TIMEOUT_SECS: u64 = 300;
+pub const READINESS_MAX_CYCLE_AGE_SECS: u64 = 180;
+const CYCLE_HISTORY: usize = 20;
+
+// NSE equity hours in exchange local time: pre-open 09:00-09:15, continuous
+// trading 09:15-15:30, Monday to Friday. Exchange holidays are not known here
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketSession {
+    PreOpen,
+    Open,
+    Closed,
+}
+
+pub fn market_session(now: NaiveDateTime) -> MarketSession {
+    if matches!(now.weekday(), Weekday::Sat | Weekday::Sun) {
+        return MarketSession::Closed;
+    }
+    let time = now.time();
+    let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    if time >= at(9, 0) && time < at(9, 15) {
+        MarketSession::PreOpen
+    } else if time >= at(9, 15) && time <= at(15, 30) {
+        MarketSession::Open
+    } else {
+        MarketSession::Closed
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+struct CircuitBreaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    last_error: Option<String>,
+}
+
+impl CircuitBreaker {
+    fn new() -> Self {
+        CircuitBreaker { state: CircuitState::Closed, consecutive_failures: 0, opened_at: None, last_error: None }
+    }
+
+    fn allow(&mut self, now: Instant) -> bool {
+        if self.state == CircuitState::Open && self.opened_at.is_some_and(|at| now.duration_since(at) >= Duration::from_secs(CIRCUIT_COOLDOWN_SECS)) {
+            self.state = CircuitState::HalfOpen;
+        }
+        self.state != CircuitState::Open
+    }
+
+    fn record(&mut self, outcome: Result<(), &str>, now: Instant) {
+        match outcome {
+            Ok(()) => {
+                self.state = CircuitState::Closed;
+                self.consecutive_failures = 0;
+                self.opened_at = None;
+            }
+            Err(error) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(error.to_string());
+                // a failed trial reopens straight away
+                if self.state == CircuitState::HalfOpen || self.consecutive_failures >= CIRCUIT_FAILURE_THRESHOLD {
+                    self.state = CircuitState::Open;
+                    self.opened_at = Some(now);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct SymbolHealth {
+    last_success_ms: Option<i64>,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+}
+
+struct HealthInner {
+    last_progress: Instant,
+    cycles_completed: u64,
+    last_cycle_completed: Option<Instant>,
+    cycle_ms: VecDeque<u64>,
+    symbols: HashMap<String, SymbolHealth>,
+    circuits: BTreeMap<String, CircuitBreaker>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CycleStats {
+    pub completed: u64,
+    pub last_completed_secs_ago: Option<u64>,
+    pub last_ms: Option<u64>,
+    pub average_ms: Option<u64>,
+    pub max_ms: Option<u64>,
+    // oldest first, at most the last 20 cycles
+    pub recent_ms: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProviderStatus {
+    pub name: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    // while open, how long until a trial fetch is let through
+    pub retry_in_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SymbolStatus {
+    pub symbol: String,
+    // unix milliseconds
+    pub last_success: Option<i64>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StatusReport {
+    pub uptime_secs: u64,
+    pub started_at: i64,
+    pub source: String,
+    pub market_session: MarketSession,
+    // includes the replay recorder; every WebSocket and SSE client adds one
+    pub broadcast_subscribers: usize,
+    pub ready: bool,
+    pub cycles: CycleStats,
+    pub providers: Vec<ProviderStatus>,
+    pub symbols: Vec<SymbolStatus>,
+}
+
+// Body of /healthz and /readyz; `reasons` says what failed when status is "unavailable"
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Probe {
+    pub status: &'static str,
+    pub reasons: Vec<String>,
+}
+
+impl Probe {
+    fn from_reasons(reasons: Vec<String>) -> Self {
+        Probe { status: if reasons.is_empty() { "ok" } else { "unavailable" }, reasons }
+    }
+
+    pub fn ok(&self) -> bool {
+        self.reasons.is_empty()
+    }
+}
+
+// What the fetch loop reports about itself, for the health, readiness and status endpoints
+pub struct EngineHealth {
+    started: Instant,
+    started_at: i64,
+    inner: Mutex<HealthInner>,
+}
+
+impl EngineHealth {
+    pub fn new(now: Instant) -> Self {
+        EngineHealth {
+            started: now,
+            started_at: chrono::Utc::now().timestamp_millis(),
+            inner: Mutex::new(HealthInner {
+                last_progress: now,
+                cycles_completed: 0,
+                last_cycle_completed: None,
+                cycle_ms: VecDeque::with_capacity(CYCLE_HISTORY),
+                symbols: HashMap::new(),
+                circuits: BTreeMap::new(),
+            }),
+        }
+    }
+
+    pub fn begin_cycle(&self, now: Instant) {
+        self.inner.lock().unwrap().last_progress = now;
+    }
+
+    // false while the provider's circuit is open; the loop skips the fetch
+    pub fn allow_fetch(&self, provider: &str, now: Instant) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        inner.last_progress = now;
+        inner.circuits.entry(provider.to_string()).or_insert_with(CircuitBreaker::new).allow(now)
+    }
+
+    pub fn record_fetch(&self, provider: &str, symbol: &str, outcome: Result<(), &str>, now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.last_progress = now;
+        inner.circuits.entry(provider.to_string()).or_insert_with(CircuitBreaker::new).record(outcome, now);
+        let health = inner.symbols.entry(symbol.to_string()).or_default();
+        match outcome {
+            Ok(()) => {
+                health.last_success_ms = Some(chrono::Utc::now().timestamp_millis());
+                health.consecutive_failures = 0;
+            }
+            Err(error) => {
+                health.consecutive_failures += 1;
+                health.last_error = Some(error.to_string());
+            }
+        }
+    }
+
+    pub fn end_cycle(&self, cycle_ms: u64, now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.last_progress = now;
+        inner.cycles_completed += 1;
+        inner.last_cycle_completed = Some(now);
+        if inner.cycle_ms.len() == CYCLE_HISTORY {
+            inner.cycle_ms.pop_front();
+        }
+        inner.cycle_ms.push_back(cycle_ms);
+    }
+
+    pub fn liveness(&self, now: Instant) -> Probe {
+        let inner = self.inner.lock().unwrap();
+        let idle = now.duration_since(inner.last_progress).as_secs();
+        let mut reasons = Vec::new();
+        if idle >= LIVENESS_TIMEOUT_SECS {
+            reasons.push(format!("fetch loop has made no progress for {}s", idle));
+        }
+        Probe::from_reasons(reasons)
+    }
+
+    // ready once a cycle has completed recently and at least one provider's circuit is not open
+    pub fn readiness(&self, now: Instant) -> Probe {
+        let mut reasons = self.liveness(now).reasons;
+        let inner = self.inner.lock().unwrap();
+        match inner.last_cycle_completed {
+            None => reasons.push("no fetch cycle has completed yet".to_string()),
+            Some(at) => {
+                let age = now.duration_since(at).as_secs();
+                if age >= READINESS_MAX_CYCLE_AGE_SECS {
+                    reasons.push(format!("last fetch cycle completed {}s ago", age));
+                }
+            }
+        }
+        if !inner.circuits.is_empty() && inner.circuits.values().all(|c| c.state == CircuitState::Open) {
+            reasons.push("every market data provider circuit is open".to_string());
+        }
+        Probe::from_reasons(reasons)
+    }
+
+    pub fn report(&self, source: &str, watchlist: &[String], market_session: MarketSession, broadcast_subscribers: usize, now: Instant) -> StatusReport {
+        let ready = self.readiness(now).ok();
+        let inner = self.inner.lock().unwrap();
+        let recent_ms: Vec<u64> = inner.cycle_ms.iter().copied().collect();
+        let cycles = CycleStats {
+            completed: inner.cycles_completed,
+            last_completed_secs_ago: inner.last_cycle_completed.map(|at| now.duration_since(at).as_secs()),
+            last_ms: recent_ms.last().copied(),
+            average_ms: (!recent_ms.is_empty()).then(|| recent_ms.iter().sum::<u64>() / recent_ms.len() as u64),
+            max_ms: recent_ms.iter().max().copied(),
+            recent_ms,
+        };
+        let providers = inner
+            .circuits
+            .iter()
+            .map(|(name, circuit)| ProviderStatus {
+                name: name.clone(),
+                state: circuit.state,
+                consecutive_failures: circuit.consecutive_failures,
+                last_error: circuit.last_error.clone(),
+                retry_in_secs: circuit
+                    .opened_at
+                    .filter(|_| circuit.state == CircuitState::Open)
+                    .map(|at| CIRCUIT_COOLDOWN_SECS.saturating_sub(now.duration_since(at).as_secs())),
+            })
+            .collect();
+        let symbols = watchlist
+            .iter()
+            .map(|symbol| {
+                let health = inner.symbols.get(symbol);
+                SymbolStatus {
+                    symbol: symbol.clone(),
+                    last_success: health.and_then(|h| h.last_success_ms),
+                    consecutive_failures: health.map_or(0, |h| h.consecutive_failures),
+                    last_error: health.and_then(|h| h.last_error.clone()),
+                }
+            })
+            .collect();
+        StatusReport {
+            uptime_secs: now.duration_since(self.started).as_secs(),
+            started_at: self.started_at,
+            source: source.to_string(),
+            market_session,
+            broadcast_subscribers,
+            ready,
+            cycles,
+            providers,
+            symbols,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_market_session() {
+        // 2025-11-24 is a Monday
+        let at = |day, h, m| NaiveDate::from_ymd_opt(2025, 11, day).unwrap().and_hms_opt(h, m, 0).unwrap();
+        assert_eq!(market_session(at(24, 9, 5)), MarketSession::PreOpen);
+        assert_eq!(market_session(at(24, 9, 15)), MarketSession::Open);
+        assert_eq!(market_session(at(24, 15, 30)), MarketSession::Open);
+        assert_eq!(market_session(at(24, 15, 31)), MarketSession::Closed);
+        assert_eq!(market_session(at(22, 11, 0)), MarketSession::Closed);
+    }
+
+    #[test]
+    fn test_circuit_opens_then_half_opens_after_cooldown() {
+        let health = EngineHealth::new(Instant::now());
+        let start = Instant::now();
+        for _ in 0..CIRCUIT_FAILURE_THRESHOLD {
+            assert!(health.allow_fetch("Yahoo", start));
+            health.record_fetch("Yahoo", "TCS", Err("timeout"), start);
+        }
+        assert!(!health.allow_fetch("Yahoo", start + Duration::from_secs(1)));
+        assert!(health.readiness(start).reasons.iter().any(|r| r.contains("circuit")));
+
+        let later = start + Duration::from_secs(CIRCUIT_COOLDOWN_SECS);
+        assert!(health.allow_fetch("Yahoo", later));
+        health.record_fetch("Yahoo", "TCS", Err("timeout"), later);
+        assert!(!health.allow_fetch("Yahoo", later));
+
+        let recovered = later + Duration::from_secs(CIRCUIT_COOLDOWN_SECS);
+        assert!(health.allow_fetch("Yahoo", recovered));
+        health.record_fetch("Yahoo", "TCS", Ok(()), recovered);
+        let report = health.report("Yahoo", &["TCS".to_string(), "INFY".to_string()], MarketSession::Open, 2, recovered);
+        assert_eq!(report.providers[0].state, CircuitState::Closed);
+        assert!(report.symbols[0].last_success.is_some());
+        assert_eq!(report.symbols[0].last_error.as_deref(), Some("timeout"));
+        assert!(report.symbols[1].last_success.is_none());
+    }
+
+    #[test]
+    fn test_readiness_needs_a_recent_cycle() {
+        let start = Instant::now();
+        let health = EngineHealth::new(start);
+        assert!(health.liveness(start).ok());
+        assert!(!health.readiness(start).ok());
+
+        health.end_cycle(1200, start);
+        health.end_cycle(800, start);
+        assert!(health.readiness(start).ok());
+        let report = health.report("Synthetic", &[], MarketSession::Closed, 1, start);
+        assert_eq!(report.cycles.average_ms, Some(1000));
+        assert_eq!(report.cycles.recent_ms, vec![1200, 800]);
+        assert!(report.ready);
+
+        let stale = start + Duration::from_secs(LIVENESS_TIMEOUT_SECS);
+        assert!(!health.liveness(stale).ok());
+        assert_eq!(health.readiness(stale).reasons.len(), 2);
+    }
+}
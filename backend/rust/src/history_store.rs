@@ -0,0 +1,239 @@
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::RwLock;
+use log::info;
+
+pub const HISTORY_SNAPSHOT_FILE: &str = "history_snapshot.json";
+const SHARDS: usize = 16;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct HistoryPoint {
+    pub timestamp_ms: i64,
+    pub spot_price: f64,
+    pub futures_price: f64,
+    pub spread_percentage: f64,
+}
+
+// One page of a time-window query, counted back from the newest point: offset 0
+// is the newest `limit` points, and next_offset continues into older ones
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct HistoryPage {
+    pub total: usize,
+    pub offset: usize,
+    pub next_offset: Option<usize>,
+    pub points: Vec<HistoryPoint>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    depth: usize,
+    symbols: HashMap<String, Vec<HistoryPoint>>,
+}
+
+// Per-symbol ring buffers of the last `depth` points, sharded by symbol so the
+// engine writing one symbol does not block readers of another
+pub struct HistoryStore {
+    depth: usize,
+    shards: Vec<RwLock<HashMap<String, VecDeque<HistoryPoint>>>>,
+}
+
+impl HistoryStore {
+    pub fn new(depth: usize) -> Self {
+        HistoryStore {
+            depth: depth.max(1),
+            shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn shard(&self, symbol: &str) -> &RwLock<HashMap<String, VecDeque<HistoryPoint>>> {
+        let mut hasher = DefaultHasher::new();
+        symbol.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+
+    // points are expected in time order; the oldest is dropped once the buffer is full
+    pub fn push(&self, symbol: &str, point: HistoryPoint) {
+        let mut shard = self.shard(symbol).write().unwrap();
+        let buffer = shard.entry(symbol.to_string()).or_insert_with(|| VecDeque::with_capacity(self.depth));
+        if buffer.len() == self.depth {
+            buffer.pop_front();
+        }
+        buffer.push_back(point);
+    }
+
+    pub fn latest(&self, symbol: &str) -> Option<HistoryPoint> {
+        self.shard(symbol).read().unwrap().get(symbol).and_then(|b| b.back().cloned())
+    }
+
+    pub fn last_n(&self, symbol: &str, n: usize) -> Vec<HistoryPoint> {
+        let shard = self.shard(symbol).read().unwrap();
+        shard
+            .get(symbol)
+            .map(|b| b.iter().skip(b.len().saturating_sub(n)).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn recent_spreads(&self, symbol: &str, n: usize) -> Vec<f64> {
+        self.last_n(symbol, n).iter().map(|p| p.spread_percentage).collect()
+    }
+
+    // points with from_ms <= timestamp_ms < to_ms, either bound optional
+    pub fn range(&self, symbol: &str, from_ms: Option<i64>, to_ms: Option<i64>) -> Vec<HistoryPoint> {
+        let shard = self.shard(symbol).read().unwrap();
+        let Some(buffer) = shard.get(symbol) else {
+            return Vec::new();
+        };
+        let start = from_ms.map_or(0, |from| buffer.partition_point(|p| p.timestamp_ms < from));
+        let end = to_ms.map_or(buffer.len(), |to| buffer.partition_point(|p| p.timestamp_ms < to));
+        buffer.range(start..end.max(start)).cloned().collect()
+    }
+
+    pub fn page(&self, symbol: &str, from_ms: Option<i64>, to_ms: Option<i64>, limit: Option<usize>, offset: usize) -> HistoryPage {
+        let mut points = self.range(symbol, from_ms, to_ms);
+        let total = points.len();
+        let end = total.saturating_sub(offset);
+        let start = limit.map_or(0, |limit| end.saturating_sub(limit));
+        points.truncate(end);
+        points.drain(..start);
+        HistoryPage {
+            total,
+            offset,
+            next_offset: (start > 0).then_some(total - start),
+            points,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.shards.iter().flat_map(|s| s.read().unwrap().keys().cloned().collect::<Vec<_>>()).collect();
+        symbols.sort();
+        symbols
+    }
+
+    #[allow(dead_code)]
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            shard.write().unwrap().clear();
+        }
+    }
+
+    // written to a temporary file first so a crash mid-write keeps the old snapshot
+    pub fn save_snapshot(&self, path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut symbols = HashMap::new();
+        for shard in &self.shards {
+            for (symbol, buffer) in shard.read().unwrap().iter() {
+                symbols.insert(symbol.clone(), buffer.iter().cloned().collect());
+            }
+        }
+        let snapshot = Snapshot { depth: self.depth, symbols };
+
+        let temp_path = format!("{}.tmp", path);
+        std::fs::write(&temp_path, serde_json::to_vec(&snapshot)?)?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    // the snapshot may have been taken with a different depth; only the newest points are kept
+    pub fn load_snapshot(path: &str, depth: usize) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let snapshot: Snapshot = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let store = HistoryStore::new(depth);
+        let mut points = 0;
+        for (symbol, mut history) in snapshot.symbols {
+            history.sort_by_key(|p| p.timestamp_ms);
+            for point in history.into_iter().rev().take(store.depth).rev() {
+                store.push(&symbol, point);
+                points += 1;
+            }
+        }
+        info!("Restored {} history points from {} (snapshot depth {})", points, path, snapshot.depth);
+        Ok(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(timestamp_ms: i64, spread_percentage: f64) -> HistoryPoint {
+        HistoryPoint {
+            timestamp_ms,
+            spot_price: 1000.0,
+            futures_price: 1000.0 * (1.0 + spread_percentage / 100.0),
+            spread_percentage,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let store = HistoryStore::new(3);
+        for i in 0..5 {
+            store.push("RELIANCE", point(i * 1000, i as f64 / 10.0));
+        }
+        let points = store.last_n("RELIANCE", 10);
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0].timestamp_ms, 2000);
+        assert_eq!(store.latest("RELIANCE").unwrap().timestamp_ms, 4000);
+        assert_eq!(store.recent_spreads("RELIANCE", 2), vec![0.3, 0.4]);
+        assert!(store.latest("TCS").is_none());
+    }
+
+    #[test]
+    fn test_time_window_queries() {
+        let store = HistoryStore::new(100);
+        for i in 0..10 {
+            store.push("TCS", point(i * 1000, 0.1));
+        }
+        assert_eq!(store.range("TCS", Some(3000), Some(6000)).len(), 3);
+        assert_eq!(store.range("TCS", Some(8500), None).len(), 1);
+        assert_eq!(store.range("TCS", None, Some(1000)).len(), 1);
+        assert!(store.range("TCS", Some(6000), Some(3000)).is_empty());
+        assert_eq!(store.symbols(), vec!["TCS".to_string()]);
+    }
+
+    #[test]
+    fn test_pages_walk_back_from_newest() {
+        let store = HistoryStore::new(100);
+        for i in 0..10 {
+            store.push("TCS", point(i * 1000, 0.1));
+        }
+        let first = store.page("TCS", None, None, Some(4), 0);
+        assert_eq!(first.total, 10);
+        assert_eq!(first.points.iter().map(|p| p.timestamp_ms).collect::<Vec<_>>(), vec![6000, 7000, 8000, 9000]);
+        assert_eq!(first.next_offset, Some(4));
+
+        let last = store.page("TCS", None, None, Some(4), 8);
+        assert_eq!(last.points.len(), 2);
+        assert_eq!(last.points[0].timestamp_ms, 0);
+        assert_eq!(last.next_offset, None);
+
+        assert_eq!(store.page("TCS", Some(5000), None, None, 0).points.len(), 5);
+        assert!(store.page("TCS", None, None, Some(4), 20).points.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let store = HistoryStore::new(10);
+        for i in 0..6 {
+            store.push("INFY", point(i * 1000, 0.2));
+            store.push("SBIN", point(i * 1000, -0.1));
+        }
+        let path = std::env::temp_dir().join(format!("history_snapshot_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        store.save_snapshot(path).unwrap();
+
+        let restored = HistoryStore::load_snapshot(path, 4).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(restored.symbols(), vec!["INFY".to_string(), "SBIN".to_string()]);
+        let points = restored.last_n("INFY", 10);
+        assert_eq!(points.len(), 4);
+        assert_eq!(points[0].timestamp_ms, 2000);
+        assert_eq!(restored.latest("SBIN"), store.latest("SBIN"));
+    }
+}
@@ -0,0 +1,332 @@
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use std::collections::HashMap;
+use crate::profit_calculator::{calculate_cash_trade_costs, calculate_futures_costs, get_lot_size};
+use crate::opportunity::{leg, new_opportunity, provenance, DataSource, Opportunity, Side, StrategyKind, ToOpportunities};
+
+pub const INDEX_WEIGHTS_FILE: &str = "index_weights.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexConstituent {
+    pub symbol: String,
+    pub weight: f64,
+    pub reference_price: f64,
+}
+
+// base_level is the index value at the moment the constituent reference_prices were taken
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexDefinition {
+    pub name: String,
+    pub yahoo_symbol: String,
+    pub futures_lot_size: u32,
+    pub base_level: f64,
+    pub constituents: Vec<IndexConstituent>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexArbitrageConfig {
+    pub threshold_percentage: f64,
+    pub risk_free_rate: f64,
+    pub max_index_lots: u32,
+}
+
+impl Default for IndexArbitrageConfig {
+    fn default() -> Self {
+        IndexArbitrageConfig {
+            threshold_percentage: 0.5,
+            risk_free_rate: 0.065,
+            max_index_lots: 50,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BasketLeg {
+    pub symbol: String,
+    pub price: f64,
+    pub lot_size: u32,
+    pub lots: u32,
+    pub quantity: u32,
+    pub notional: f64,
+    pub target_weight: f64,
+    pub actual_weight: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct IndexArbitrageResult {
+    pub opportunity: bool,
+    pub index: String,
+    pub index_spot: f64,
+    pub synthetic_index: f64,
+    pub futures_price: f64,
+    pub fair_futures_price: f64,
+    pub basis: f64,
+    pub mispricing_percentage: f64,
+    pub action: String,
+    pub index_lots: u32,
+    pub futures_lot_size: u32,
+    pub futures_notional: f64,
+    pub basket_notional: f64,
+    pub basket: Vec<BasketLeg>,
+    pub tracking_error_pct: f64,
+    pub synthetic_vs_spot_pct: f64,
+    pub gross_profit: f64,
+    pub missing_constituents: Vec<String>,
+    pub expiry: String,
+    pub details: String,
+    pub last_update: String,
+}
+
+pub fn load_index_definitions(path: &str) -> Result<Vec<IndexDefinition>, Box<dyn std::error::Error + Send + Sync>> {
+    let text = std::fs::read_to_string(path)?;
+    let definitions: Vec<IndexDefinition> = serde_json::from_str(&text)?;
+
+    for definition in &definitions {
+        if definition.constituents.iter().any(|c| c.weight <= 0.0 || c.reference_price <= 0.0) {
+            return Err(format!("{} has a constituent with a non-positive weight or reference price", definition.name).into());
+        }
+    }
+
+    Ok(definitions)
+}
+
+// Constituents without a live price are dropped and the remaining weights renormalised
+fn available_weights(definition: &IndexDefinition, spots: &HashMap<String, f64>) -> (Vec<(IndexConstituent, f64, f64)>, Vec<String>) {
+    let mut available = Vec::new();
+    let mut missing = Vec::new();
+
+    for constituent in &definition.constituents {
+        match spots.get(&constituent.symbol) {
+            Some(&price) if price > 0.0 => available.push((constituent.clone(), price)),
+            _ => missing.push(constituent.symbol.clone()),
+        }
+    }
+
+    let total_weight: f64 = available.iter().map(|(c, _)| c.weight).sum();
+    let weighted = available
+        .into_iter()
+        .map(|(c, price)| {
+            let weight = c.weight / total_weight;
+            (c, weight, price)
+        })
+        .collect();
+
+    (weighted, missing)
+}
+
+pub fn synthetic_index_value(definition: &IndexDefinition, spots: &HashMap<String, f64>) -> Option<f64> {
+    let (weighted, _) = available_weights(definition, spots);
+    if weighted.is_empty() {
+        return None;
+    }
+
+    let relative: f64 = weighted.iter().map(|(c, weight, price)| weight * price / c.reference_price).sum();
+    Some(definition.base_level * relative)
+}
+
+fn build_basket(weighted: &[(IndexConstituent, f64, f64)], futures_notional: f64) -> (Vec<BasketLeg>, f64) {
+    let mut legs = Vec::with_capacity(weighted.len());
+    let mut squared_error = 0.0;
+
+    for (constituent, target_weight, price) in weighted {
+        let lot_size = get_lot_size(&constituent.symbol);
+        let ideal_quantity = futures_notional * target_weight / price;
+        let lots = (ideal_quantity / lot_size as f64).round() as u32;
+        let quantity = lots * lot_size;
+        let notional = quantity as f64 * price;
+        let actual_weight = notional / futures_notional;
+
+        squared_error += (actual_weight - target_weight).powi(2);
+        legs.push(BasketLeg {
+            symbol: constituent.symbol.clone(),
+            price: *price,
+            lot_size,
+            lots,
+            quantity,
+            notional,
+            target_weight: target_weight * 100.0,
+            actual_weight: actual_weight * 100.0,
+        });
+    }
+
+    (legs, squared_error.sqrt() * 100.0)
+}
+
+pub fn detect_index_arbitrage(
+    definition: &IndexDefinition,
+    spots: &HashMap<String, f64>,
+    index_spot: f64,
+    futures_price: f64,
+    time_to_expiry_years: f64,
+    config: &IndexArbitrageConfig,
+) -> Option<IndexArbitrageResult> {
+    let threshold_percentage = config.threshold_percentage;
+    let synthetic_index = synthetic_index_value(definition, spots)?;
+    let (weighted, missing_constituents) = available_weights(definition, spots);
+
+    let fair_futures_price = synthetic_index * (1.0 + config.risk_free_rate * time_to_expiry_years);
+    let basis = futures_price - synthetic_index;
+    let mispricing_percentage = (futures_price - fair_futures_price) / fair_futures_price * 100.0;
+
+    let opportunity = mispricing_percentage.abs() > threshold_percentage;
+    let action = if mispricing_percentage > threshold_percentage {
+        "BUY Basket, SELL Index Futures".to_string()
+    }
+    else if mispricing_percentage < -threshold_percentage {
+        "SELL Basket, BUY Index Futures".to_string()
+    }
+    else {
+        "HOLD".to_string()
+    };
+
+    // stock lots are coarse next to an index lot, so pick the index lot count the basket tracks best
+    let mut best: Option<(u32, Vec<BasketLeg>, f64)> = None;
+    for index_lots in 1..=config.max_index_lots.max(1) {
+        let futures_notional = index_lots as f64 * definition.futures_lot_size as f64 * futures_price;
+        let (legs, tracking_error) = build_basket(&weighted, futures_notional);
+        if best.as_ref().is_none_or(|(_, _, best_error)| tracking_error < *best_error) {
+            best = Some((index_lots, legs, tracking_error));
+        }
+    }
+    let (index_lots, basket, tracking_error_pct) = best?;
+
+    let futures_notional = index_lots as f64 * definition.futures_lot_size as f64 * futures_price;
+    let basket_notional = basket.iter().map(|leg| leg.notional).sum();
+    let gross_profit = (futures_price - fair_futures_price).abs() * (index_lots * definition.futures_lot_size) as f64;
+    let synthetic_vs_spot_pct = if index_spot > 0.0 {
+        (synthetic_index - index_spot) / index_spot * 100.0
+    } else {
+        0.0
+    };
+
+    let details = if opportunity {
+        format!(
+            "Index arbitrage on {}! Synthetic: {:.2}, Futures: {:.2}, Fair: {:.2}, Mispricing: {:.2}% over {} lot(s), tracking error {:.2}%",
+            definition.name, synthetic_index, futures_price, fair_futures_price, mispricing_percentage, index_lots, tracking_error_pct
+        )
+    } else {
+        format!(
+            "No index arbitrage on {}. Synthetic: {:.2}, Futures: {:.2}, Fair: {:.2}, Mispricing: {:.2}%",
+            definition.name, synthetic_index, futures_price, fair_futures_price, mispricing_percentage
+        )
+    };
+
+    Some(IndexArbitrageResult {
+        opportunity,
+        index: definition.name.clone(),
+        index_spot,
+        synthetic_index,
+        futures_price,
+        fair_futures_price,
+        basis,
+        mispricing_percentage,
+        action,
+        index_lots,
+        futures_lot_size: definition.futures_lot_size,
+        futures_notional,
+        basket_notional,
+        basket,
+        tracking_error_pct,
+        synthetic_vs_spot_pct,
+        gross_profit,
+        missing_constituents,
+        expiry: String::new(),
+        details,
+        last_update: chrono::Local::now().format("%H:%M:%S").to_string(),
+    })
+}
+
+impl ToOpportunities for IndexArbitrageResult {
+    fn to_opportunities(&self, source: DataSource) -> Vec<Opportunity> {
+        if !self.opportunity {
+            return Vec::new();
+        }
+
+        let (basket_side, futures_side) = if self.mispricing_percentage > 0.0 { (Side::Buy, Side::Sell) } else { (Side::Sell, Side::Buy) };
+        let mut legs: Vec<_> = self.basket
+            .iter()
+            .filter(|b| b.quantity > 0)
+            .map(|b| leg(&b.symbol, basket_side, b.quantity, b.price))
+            .collect();
+        legs.push(leg(format!("{} FUT", self.index), futures_side, self.index_lots * self.futures_lot_size, self.futures_price));
+
+        // basket bought now and sold at expiry, plus the futures round trip
+        let costs = calculate_cash_trade_costs(self.basket_notional, self.basket_notional)
+            + calculate_futures_costs(self.futures_notional);
+
+        let mut opportunity = new_opportunity(StrategyKind::IndexBasket, &self.index, legs, self.gross_profit, costs, provenance("index_arbitrage", source, &["futures_price"]));
+        opportunity.expiry = (!self.expiry.is_empty()).then(|| self.expiry.clone());
+        opportunity.details = self.details.clone();
+        vec![opportunity]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opportunity::YAHOO_DELAYED;
+
+    fn bank_index() -> IndexDefinition {
+        IndexDefinition {
+            name: "BANKNIFTY".to_string(),
+            yahoo_symbol: "^NSEBANK".to_string(),
+            futures_lot_size: 35,
+            base_level: 54000.0,
+            constituents: vec![
+                IndexConstituent { symbol: "HDFCBANK".to_string(), weight: 30.0, reference_price: 800.0 },
+                IndexConstituent { symbol: "ICICIBANK".to_string(), weight: 25.0, reference_price: 1400.0 },
+                IndexConstituent { symbol: "SBIN".to_string(), weight: 10.0, reference_price: 1000.0 },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_synthetic_index_tracks_constituents() {
+        let definition = bank_index();
+        let mut spots = HashMap::from([
+            ("HDFCBANK".to_string(), 800.0),
+            ("ICICIBANK".to_string(), 1400.0),
+            ("SBIN".to_string(), 1000.0),
+        ]);
+        assert!((synthetic_index_value(&definition, &spots).unwrap() - 54000.0).abs() < 1e-6);
+
+        // every constituent up 2% moves the synthetic index up 2%
+        for price in spots.values_mut() {
+            *price *= 1.02;
+        }
+        assert!((synthetic_index_value(&definition, &spots).unwrap() - 55080.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rich_futures_sells_futures_against_basket() {
+        let definition = bank_index();
+        let spots = HashMap::from([
+            ("HDFCBANK".to_string(), 800.0),
+            ("ICICIBANK".to_string(), 1400.0),
+            ("SBIN".to_string(), 1000.0),
+        ]);
+
+        let result = detect_index_arbitrage(&definition, &spots, 54000.0, 55000.0, 0.05, &IndexArbitrageConfig::default()).unwrap();
+        assert!(result.opportunity);
+        assert_eq!(result.action, "BUY Basket, SELL Index Futures");
+        assert_eq!(result.basket.len(), 3);
+        for leg in &result.basket {
+            assert_eq!(leg.quantity % leg.lot_size, 0);
+        }
+        assert!(result.tracking_error_pct < 10.0);
+
+        let opportunities = result.to_opportunities(YAHOO_DELAYED);
+        assert_eq!(opportunities[0].kind, StrategyKind::IndexBasket);
+        assert_eq!(opportunities[0].legs.last().unwrap().side, Side::Sell);
+    }
+
+    #[test]
+    fn test_missing_constituent_is_reported() {
+        let definition = bank_index();
+        let spots = HashMap::from([("HDFCBANK".to_string(), 800.0), ("ICICIBANK".to_string(), 1400.0)]);
+
+        let result = detect_index_arbitrage(&definition, &spots, 54000.0, 54100.0, 0.05, &IndexArbitrageConfig::default()).unwrap();
+        assert_eq!(result.missing_constituents, vec!["SBIN".to_string()]);
+        assert!(!result.opportunity);
+    }
+}
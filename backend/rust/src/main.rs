@@ -6,28 +6,160 @@ mod data_logger;
 
 mod backtester;
 mod options_arbitrage;
+mod volatility_surface;
+mod index_arbitrage;
+mod cross_exchange_arbitrage;
+mod stat_arbitrage;
+mod opportunity;
+mod strategy_backtester;
+mod walk_forward;
+mod synthetic_market;
+mod market_data;
+mod history_store;
+mod engine_state;
+mod ws_protocol;
+mod feed;
+mod feed_replay;
+mod validation;
+mod openapi;
+mod auth;
+mod health;
 
 use warp::Filter;
 use warp::ws::{Message, WebSocket};
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 use log::{info, error, warn};
-use nse_data_api::{fetch_nse_spot_price, fetch_nse_futures_price, create_nse_client};
+use nse_data_api::{upcoming_monthly_expiries, Exchange};
 use arbitrage_detector::{detect_cash_futures_arbitrage, ArbitrageResult};
-use trend_tracker::{create_spread_tracker, calculate_trend, SpreadHistory};
-use backtester::{run_monte_carlo, BacktestParams};
-use data_logger::{initialize_csv_log, log_to_csv};
+use trend_tracker::{create_spread_tracker, calculate_trend, preview_trend, trend_config_from_env, SpreadHistory, TrendConfig};
+use backtester::{run_monte_carlo, total_paths, BacktestParams, BacktestResponse, RunControl, SimulationMode};
+use data_logger::{initialize_csv_log, log_to_csv, log_opportunity, load_logged_observations, LOG_FILE};
+use options_arbitrage::{OptionContract, PcpRequest};
+use validation::{parse_request, FieldError};
+use volatility_surface::{scan_volatility_surface, time_to_expiry_years, SurfaceConfig};
+use index_arbitrage::{detect_index_arbitrage, load_index_definitions, IndexArbitrageConfig, IndexArbitrageResult, IndexDefinition, INDEX_WEIGHTS_FILE};
+use cross_exchange_arbitrage::{detect_cross_exchange_arbitrage, CrossExchangeConfig, CrossExchangeResult};
+use stat_arbitrage::{StatArbConfig, StatArbTracker};
+use opportunity::{DataSource, StrategyKind, ToOpportunities};
+use strategy_backtester::{run_strategy_backtest, StrategyBacktestParams};
+use walk_forward::{run_walk_forward, WalkForwardParams};
+use market_data::{market_data_from_env, MarketDataSource};
+use history_store::{HistoryPage, HistoryPoint, HistoryStore, HISTORY_SNAPSHOT_FILE};
+use engine_state::{normalize_symbol, EngineState};
+use ws_protocol::{classify_feed_message, parse_command, snapshot_frame, Sequencer, Subscription, Throttle, HEARTBEAT_INTERVAL_SECS, IDLE_TIMEOUT_SECS};
+use feed::{Alert, AlertLevel, DetectorResult, Envelope, FeedMessage, Heartbeat, StatusUpdate};
+use feed_replay::{Replay, REPLAY_CAPACITY};
+use auth::{auth_config_from_env, AuthConfig, Authenticator, Scope};
+use health::{market_session, MarketSession, Probe};
+use std::collections::HashMap;
 use std::convert::Infallible;
+use std::sync::Arc;
 use tokio::sync::broadcast;
 use tokio::time::{sleep, Duration};
 use futures::{SinkExt, StreamExt};
-use chrono::Timelike;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 struct ArbitrageResponse {
     opportunity: bool,
     details: String,
 }
 
+#[derive(Deserialize, JsonSchema)]
+struct ArbitrageQuery {
+    #[serde(default)]
+    refresh: bool,
+}
+
+// from and to are unix milliseconds; limit is the page size and offset counts
+// back from the newest point
+#[derive(Deserialize, JsonSchema)]
+struct HistoryQuery {
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct OpportunitiesQuery {
+    limit: Option<usize>,
+    symbol: Option<String>,
+    kind: Option<StrategyKind>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct WatchlistRequest {
+    symbol: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct HistoryResponse {
+    symbol: String,
+    depth: usize,
+    #[serde(flatten)]
+    page: HistoryPage,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct SymbolState {
+    symbol: String,
+    latest: Option<ArbitrageResult>,
+}
+
+const DEFAULT_OPPORTUNITIES_LIMIT: usize = 50;
+
+// last_event_id is for clients that cannot set the Last-Event-ID header
+#[derive(Deserialize, JsonSchema)]
+struct StreamQuery {
+    last_event_id: Option<u64>,
+}
+
+// without `after`, waits for the next message; timeout_secs is capped at MAX_POLL_SECS
+#[derive(Deserialize, JsonSchema)]
+struct PollQuery {
+    after: Option<u64>,
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct PollResponse<'a> {
+    last_event_id: u64,
+    missed: u64,
+    events: Vec<Envelope<'a>>,
+}
+
+const DEFAULT_POLL_SECS: u64 = 25;
+const MAX_POLL_SECS: u64 = 60;
+
+#[derive(Deserialize, JsonSchema)]
+struct BacktestQuery {
+    #[serde(default)]
+    stream: bool,
+}
+
+// Cancels the simulation when the request that started it goes away,
+// e.g. the client disconnects mid-run
+struct CancelOnDrop(Arc<RunControl>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+type BacktestOutcome = Result<BacktestResponse, Box<dyn std::error::Error + Send + Sync>>;
+
+#[derive(Deserialize, JsonSchema)]
+struct SurfaceRequest {
+    symbol: String,
+    spot: f64,
+    chain: Vec<OptionContract>,
+    #[serde(default)]
+    config: SurfaceConfig,
+}
+
 const STOCKS_TO_MONITOR: &[&str] = &[
     "RELIANCE", "TCS", "INFY", "HDFCBANK", "ICICIBANK",
     "SBIN", "BHARTIARTL", "ITC", "KOTAKBANK", "LT",
@@ -36,151 +168,824 @@ const STOCKS_TO_MONITOR: &[&str] = &[
 
 const FUTURES_EXPIRY: &str = "28-Nov-2025";
 const THRESHOLD_PERCENTAGE: f64 = 0.5;
+const OPTION_EXPIRIES_TO_SCAN: usize = 3;
+const HISTORY_DEPTH: usize = 5000;
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
     info!("Starting NSE Arbitrage Engine with Yahoo Finance...");
 
-    let now = chrono::Local::now();
-    let hour = now.hour();
-    let minute = now.minute();
-    
-    if hour < 9 || (hour == 9 && minute < 15) || hour > 15 || (hour == 15 && minute > 30) {
-        info!("Market is closed. Using last traded prices from Yahoo Finance (15-min delayed).");
+    if market_session(chrono::Local::now().naive_local()) == MarketSession::Open {
+        info!("Market is open. Fetching Yahoo Finance data (15-min delayed).");
     }
     else {
-        info!("Market is open. Fetching Yahoo Finance data (15-min delayed).");
+        info!("Market is closed. Using last traded prices from Yahoo Finance (15-min delayed).");
     }
 
-    let (tx, _rx) = broadcast::channel::<String>(100);
+    let (tx, _rx) = broadcast::channel::<FeedMessage>(100);
     let tx_clone = tx.clone();
     
     initialize_csv_log();
+
+    let mut stat_arb_tracker = StatArbTracker::new(StatArbConfig::default());
+    match load_logged_observations(LOG_FILE) {
+        Ok(observations) => {
+            for observation in &observations {
+                stat_arb_tracker.record_price(&observation.symbol, observation.timestamp.and_utc().timestamp(), observation.spot_price);
+            }
+            info!("Loaded {} stored observations for stat-arb calibration", observations.len());
+        }
+        Err(e) => warn!("No stored price history for stat-arb: {:?}", e),
+    }
     
-    let spread_history = create_spread_tracker();
-    let spread_history_clone = spread_history.clone();
+    let spread_history: SpreadHistory = match HistoryStore::load_snapshot(HISTORY_SNAPSHOT_FILE, HISTORY_DEPTH) {
+        Ok(store) => Arc::new(store),
+        Err(e) => {
+            warn!("Starting with empty spread history, could not load {}: {}", HISTORY_SNAPSHOT_FILE, e);
+            create_spread_tracker(HISTORY_DEPTH)
+        }
+    };
+
+    let index_definitions = match load_index_definitions(INDEX_WEIGHTS_FILE) {
+        Ok(definitions) => {
+            info!("Loaded {} index definitions from {}", definitions.len(), INDEX_WEIGHTS_FILE);
+            definitions
+        }
+        Err(e) => {
+            warn!("Index arbitrage disabled, could not load {}: {:?}", INDEX_WEIGHTS_FILE, e);
+            Vec::new()
+        }
+    };
+
+    let reference_prices: HashMap<String, f64> = index_definitions
+        .iter()
+        .flat_map(|d| d.constituents.iter().map(|c| (c.symbol.clone(), c.reference_price)))
+        .collect();
+    let market_data = match market_data_from_env(STOCKS_TO_MONITOR, &reference_prices) {
+        Ok(source) => source,
+        Err(e) => {
+            error!("Invalid market data configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let auth_config = match auth_config_from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid auth configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let trend_config = match trend_config_from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid trend configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let engine = Arc::new(EngineState::new(market_data, spread_history, trend_config, STOCKS_TO_MONITOR));
+    let loop_engine = engine.clone();
+
+    // every broadcast message gets a feed-wide id in the replay buffer that backs
+    // /api/stream and /api/stream/poll
+    let mut replay_rx = tx.subscribe();
+    let replay_engine = engine.clone();
+    tokio::spawn(async move {
+        loop {
+            match replay_rx.recv().await {
+                Ok(message) => {
+                    replay_engine.feed_replay.push(message);
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => warn!("Replay buffer lost {} feed messages", missed),
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
 
     tokio::spawn(async move {
-        let client = create_nse_client();
-        let mut retry_count = std::collections::HashMap::new();
+        let engine = loop_engine;
+        let market_data = &engine.market_data;
+        let mut retry_count = HashMap::new();
+        let surface_config = SurfaceConfig::default();
+        let index_config = IndexArbitrageConfig::default();
+        let cross_exchange_config = CrossExchangeConfig::default();
         
+        let mut cycle = 0;
         loop {
-            info!("Starting new fetch cycle for {} stocks...", STOCKS_TO_MONITOR.len());
+            cycle += 1;
+            let cycle_started = std::time::Instant::now();
+            let mut symbols_fetched = 0;
+            let mut symbols_failed = 0;
+            let watchlist = engine.watchlist();
+            engine.health.begin_cycle(cycle_started);
+            info!("Starting new fetch cycle for {} stocks from {}...", watchlist.len(), market_data.name());
+            market_data.advance();
+            let today = chrono::Local::now().date_naive();
+            let option_expiries = upcoming_monthly_expiries(today, OPTION_EXPIRIES_TO_SCAN);
+            let mut latest_spots = HashMap::new();
             
-            for symbol in STOCKS_TO_MONITOR {
+            for symbol in watchlist.iter().map(String::as_str) {
+                // the watchlist can change while the cycle sleeps between symbols
+                if !engine.is_watched(symbol) {
+                    continue;
+                }
+                if !engine.health.allow_fetch(market_data.name(), std::time::Instant::now()) {
+                    warn!("Skipping {}: {} circuit is open", symbol, market_data.name());
+                    symbols_failed += 1;
+                    continue;
+                }
                 let retries = retry_count.entry(symbol.to_string()).or_insert(0);
                 
-                match check_arbitrage(&client, symbol, FUTURES_EXPIRY, &spread_history_clone).await {
+                match check_arbitrage(market_data, symbol, FUTURES_EXPIRY, &engine.spread_history, &engine.trend_config, true).await {
                     Ok(result) => {
+                        engine.health.record_fetch(market_data.name(), symbol, Ok(()), std::time::Instant::now());
+                        if !engine.record_result(&result) {
+                            info!("Dropping {}: removed from the watchlist during the fetch", symbol);
+                            continue;
+                        }
+                        symbols_fetched += 1;
                         log_to_csv(&result);
-                        let json = serde_json::to_string(&result).unwrap();
-                        let _ = tx_clone.send(json);
+                        let _ = tx_clone.send(FeedMessage::Quote(Box::new(result.clone())));
+                        publish_opportunities(&result, market_data.data_source(), &tx_clone);
                         info!("✓ Successfully fetched {} (Spread: {:.2}%)", symbol, result.spread_percentage);
                         *retries = 0;
+                        latest_spots.insert(symbol.to_string(), result.spot_price);
+                        stat_arb_tracker.record_price(symbol, chrono::Local::now().naive_local().and_utc().timestamp(), result.spot_price);
+
+                        if let Some(chain) = market_data.option_chain(symbol, result.spot_price, &option_expiries, surface_config.risk_free_rate, today) {
+                            let scan = scan_volatility_surface(symbol, result.spot_price, &chain, today, &surface_config);
+                            if scan.has_anomalies {
+                                info!("Volatility surface anomalies for {}: {}", symbol, scan.anomalies.len());
+                            }
+                            publish_detection(DetectorResult::VolatilitySurface(scan), market_data.data_source(), &tx_clone);
+                        }
+
+                        match check_cross_exchange(market_data, symbol, &cross_exchange_config).await {
+                            Ok(cross) => {
+                                if cross.opportunity {
+                                    info!("✓ {}", cross.details);
+                                }
+                                publish_detection(DetectorResult::CrossExchange(cross), market_data.data_source(), &tx_clone);
+                            }
+                            Err(e) => warn!("✗ Failed NSE/BSE check for {}: {:?}", symbol, e),
+                        }
                     }
                     Err(e) => {
+                        engine.health.record_fetch(market_data.name(), symbol, Err(&e.to_string()), std::time::Instant::now());
                         *retries += 1;
+                        symbols_failed += 1;
                         error!("✗ Failed to fetch {} (attempt {}): {:?}", symbol, retries, e);
                         
-                        if *retries > 3 {
+                        let level = if *retries > 3 {
                             warn!("Skipping {} after {} failed attempts", symbol, retries);
-                        }
+                            AlertLevel::Critical
+                        } else {
+                            AlertLevel::Warning
+                        };
+                        let _ = tx_clone.send(FeedMessage::Alert(Alert {
+                            level,
+                            symbol: Some(symbol.to_string()),
+                            message: format!("fetch failed (attempt {}): {}", retries, e),
+                        }));
                     }
                 }
                 
                 sleep(Duration::from_millis(2000)).await;
             }
             
+            for definition in &index_definitions {
+                match check_index_arbitrage(market_data, definition, &latest_spots, &option_expiries[0], today, &index_config).await {
+                    Ok(Some(result)) => {
+                        info!("✓ {} synthetic {:.2} vs futures {:.2} (Mispricing: {:.2}%)", result.index, result.synthetic_index, result.futures_price, result.mispricing_percentage);
+                        publish_detection(DetectorResult::IndexBasket(result), market_data.data_source(), &tx_clone);
+                    }
+                    Ok(None) => warn!("No constituent prices this cycle for {}", definition.name),
+                    Err(e) => error!("✗ Failed index arbitrage check for {}: {:?}", definition.name, e),
+                }
+            }
+            
+            for signal in stat_arb_tracker.on_cycle() {
+                info!("Stat-arb {}", signal.details);
+                publish_detection(DetectorResult::StatisticalArbitrage(signal), market_data.data_source(), &tx_clone);
+            }
+            
+            let snapshot_history = engine.spread_history.clone();
+            if let Ok(Err(e)) = tokio::task::spawn_blocking(move || snapshot_history.save_snapshot(HISTORY_SNAPSHOT_FILE)).await {
+                error!("Failed to save history snapshot: {}", e);
+            }
+
+            let cycle_ms = cycle_started.elapsed().as_millis() as u64;
+            engine.health.end_cycle(cycle_ms, std::time::Instant::now());
+            let _ = tx_clone.send(FeedMessage::Status(StatusUpdate {
+                source: market_data.name().to_string(),
+                cycle,
+                symbols_fetched,
+                symbols_failed,
+                cycle_ms,
+            }));
+
             info!("Cycle complete. Waiting 10 seconds before next cycle...");
             sleep(Duration::from_secs(10)).await;
         }
     });
 
-    let tx_filter = warp::any().map(move || tx.clone());
-    let ws_route = warp::path("ws").and(warp::ws()).and(tx_filter).map(|ws: warp::ws::Ws, tx: broadcast::Sender<String>| {
-                    ws.on_upgrade(move |socket| handle_ws_connection(socket, tx))
+    let routes = routes(engine, tx, auth_config);
+
+    info!("Server running on http://127.0.0.1:3030");
+    info!("WebSocket endpoint: ws://127.0.0.1:3030/ws");
+    info!("SSE endpoint: http://127.0.0.1:3030/api/stream");
+    info!("OpenAPI document: http://127.0.0.1:3030/api/openapi.json");
+    info!("Health: http://127.0.0.1:3030/healthz, /readyz and /api/status");
+    
+    warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
+}
+
+// per-trade returns from replaying the logged history with the default strategy rules
+fn routes(engine: Arc<EngineState>, tx: broadcast::Sender<FeedMessage>, auth_config: AuthConfig) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone + Send + Sync + 'static {
+    let authenticator = Arc::new(Authenticator::new(auth_config.clone()));
+
+    let ws_tx = tx.clone();
+    let tx_filter = warp::any().map(move || ws_tx.clone());
+    let ws_engine = engine.clone();
+    let ws_route = warp::path("ws")
+        .and(warp::ws())
+        .and(tx_filter)
+        .and(warp::any().map(move || ws_engine.clone()))
+        .map(|ws: warp::ws::Ws, tx: broadcast::Sender<FeedMessage>, engine: Arc<EngineState>| {
+            ws.on_upgrade(move |socket| handle_ws_connection(socket, tx, engine))
         });
 
-    let arbitrage_route = warp::path("arbitrage").and(warp::path::param::<String>()).and(warp::get()).and_then(handle_arbitrage_check);
+    let route_engine = engine.clone();
+    let refresh_auth = authenticator.clone();
+    // refresh is read from the parsed query, so any spelling of refresh=true that
+    // reaches the handler has passed the trading check
+    let arbitrage_route = warp::path("arbitrage")
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<ArbitrageQuery>())
+        .and(auth::credentials())
+        .and_then(move |symbol: String, query: ArbitrageQuery, token: Option<String>| {
+            let auth = refresh_auth.clone();
+            async move {
+                if query.refresh {
+                    auth.permit(token.as_deref(), Scope::Trading).map_err(warp::reject::custom)?;
+                }
+                Ok::<_, warp::Rejection>((symbol, query))
+            }
+        })
+        .untuple_one()
+        .and(warp::any().map(move || route_engine.clone()))
+        .and_then(handle_arbitrage_check);
 
     let backtest_route = warp::path("api")
         .and(warp::path("backtest"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::query::<BacktestQuery>())
+        .and(warp::body::json())
+        .and_then(handle_backtest);
+
+    let strategy_backtest_route = warp::path("api")
+        .and(warp::path("backtest"))
+        .and(warp::path("strategy"))
+        .and(warp::path::end())
         .and(warp::post())
         .and(warp::body::json())
-        .map(|params: BacktestParams| {
-            let result = run_monte_carlo(params);
-            warp::reply::json(&result)
+        .and_then(handle_strategy_backtest);
+
+    let history_store = engine.spread_history.clone();
+    let history_route = warp::path("api")
+        .and(warp::path("history"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<HistoryQuery>())
+        .map(move |symbol: String, query: HistoryQuery| {
+            if history_store.latest(&symbol).is_none() {
+                return warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({ "error": format!("no history for {}", symbol) })),
+                    warp::http::StatusCode::NOT_FOUND,
+                );
+            }
+            let body = HistoryResponse {
+                page: history_store.page(&symbol, query.from, query.to, query.limit, query.offset),
+                depth: history_store.depth(),
+                symbol,
+            };
+            warp::reply::with_status(warp::reply::json(&body), warp::http::StatusCode::OK)
         });
 
+    let symbols_engine = engine.clone();
+    let symbols_route = warp::path("api")
+        .and(warp::path("symbols"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(move || {
+            let symbols: Vec<SymbolState> = symbols_engine
+                .watchlist()
+                .into_iter()
+                .map(|symbol| SymbolState { latest: symbols_engine.latest_result(&symbol), symbol })
+                .collect();
+            warp::reply::json(&symbols)
+        });
+
+    let opportunities_engine = engine.clone();
+    let opportunities_route = warp::path("api")
+        .and(warp::path("opportunities"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<OpportunitiesQuery>())
+        .map(move |query: OpportunitiesQuery| {
+            let limit = query.limit.unwrap_or(DEFAULT_OPPORTUNITIES_LIMIT).min(REPLAY_CAPACITY);
+            let opportunities = opportunities_engine.feed_replay.recent_opportunities(limit, query.symbol.as_deref(), query.kind);
+            warp::reply::json(&opportunities)
+        });
+
+    let watchlist_engine = engine.clone();
+    let watchlist_get_route = warp::path("api")
+        .and(warp::path("watchlist"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(move || warp::reply::json(&watchlist_engine.watchlist()));
+
+    let watchlist_engine = engine.clone();
+    let watchlist_add_route = warp::path("api")
+        .and(warp::path("watchlist"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(auth::require(authenticator.clone(), Scope::Trading))
+        .and(warp::body::json())
+        .and(warp::any().map(move || watchlist_engine.clone()))
+        .and_then(handle_watch);
+
+    let watchlist_engine = engine.clone();
+    let watchlist_remove_route = warp::path("api")
+        .and(warp::path("watchlist"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(auth::require(authenticator.clone(), Scope::Trading))
+        .map(move |symbol: String| {
+            let symbol = match normalize_symbol(&symbol) {
+                Ok(symbol) => symbol,
+                Err(e) => return warp::reply::with_status(warp::reply::json(&serde_json::json!({ "error": e })), warp::http::StatusCode::BAD_REQUEST),
+            };
+            if !watchlist_engine.unwatch(&symbol) {
+                return warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({ "error": format!("{} is not on the watchlist", symbol) })),
+                    warp::http::StatusCode::NOT_FOUND,
+                );
+            }
+            info!("Removed {} from the watchlist", symbol);
+            warp::reply::with_status(warp::reply::json(&watchlist_engine.watchlist()), warp::http::StatusCode::OK)
+        });
+
+    let stream_engine = engine.clone();
+    let stream_route = warp::path("api")
+        .and(warp::path("stream"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::header::optional::<u64>("last-event-id"))
+        .and(warp::query::<StreamQuery>())
+        .and(warp::any().map(move || stream_engine.clone()))
+        .map(|header_id: Option<u64>, query: StreamQuery, engine: Arc<EngineState>| {
+            let events = feed_stream(engine, header_id.or(query.last_event_id));
+            warp::sse::reply(warp::sse::keep_alive().stream(events))
+        });
+
+    let poll_engine = engine.clone();
+    let poll_route = warp::path("api")
+        .and(warp::path("stream"))
+        .and(warp::path("poll"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<PollQuery>())
+        .and(warp::any().map(move || poll_engine.clone()))
+        .and_then(handle_poll);
+
+    let walk_forward_route = warp::path("api")
+        .and(warp::path("backtest"))
+        .and(warp::path("walk-forward"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(handle_walk_forward);
+
+    // ad-hoc checks run on caller-supplied prices, so the result goes back to the
+    // caller only and never onto the shared feed or the opportunity log
     let pcp_route = warp::path("api")
         .and(warp::path("options"))
         .and(warp::path("pcp"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(auth::require(authenticator.clone(), Scope::Trading))
+        .and(warp::body::json())
+        .map(|body: serde_json::Value| {
+            let inputs = match parse_request::<PcpRequest>(body) {
+                Ok(inputs) => inputs,
+                Err(errors) => return field_errors_reply(errors),
+            };
+            let opp = inputs.detect();
+            warp::reply::with_status(warp::reply::json(&opp), warp::http::StatusCode::OK)
+        });
+
+    let surface_route = warp::path("api")
+        .and(warp::path("options"))
+        .and(warp::path("surface"))
+        .and(warp::path::end())
         .and(warp::post())
         .and(warp::body::json())
-        .map(|req: serde_json::Value| {
-            let spot = req["spot"].as_f64().unwrap_or(0.0);
-            let futures = req["futures"].as_f64().unwrap_or(0.0);
-            let call = req["call_price"].as_f64().unwrap_or(0.0);
-            let put = req["put_price"].as_f64().unwrap_or(0.0);
-            let strike = req["strike"].as_f64().unwrap_or(0.0);
-            let r = req["risk_free_rate"].as_f64().unwrap_or(0.05);
-            let t = req["time_to_expiry_years"].as_f64().unwrap_or(0.08);
-
-            let opp = options_arbitrage::detect_put_call_parity(spot, futures, call, put, strike, r, t);
-            warp::reply::json(&opp)
+        .map(|req: SurfaceRequest| {
+            let today = chrono::Local::now().date_naive();
+            let scan = scan_volatility_surface(&req.symbol, req.spot, &req.chain, today, &req.config);
+            warp::reply::json(&scan)
         });
 
-    let routes = ws_route.or(arbitrage_route).or(backtest_route).or(pcp_route)
-        .with(warp::cors().allow_any_origin().allow_headers(vec!["content-type"]).allow_methods(vec!["GET", "POST"]));
+    let health_engine = engine.clone();
+    let healthz_route = warp::path("healthz")
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(move || probe_reply(health_engine.health.liveness(std::time::Instant::now())));
 
-    info!("Server running on http://127.0.0.1:3030");
-    info!("WebSocket endpoint: ws://127.0.0.1:3030/ws");
-    
-    warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
+    let ready_engine = engine.clone();
+    let readyz_route = warp::path("readyz")
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(move || probe_reply(ready_engine.health.readiness(std::time::Instant::now())));
+
+    let status_engine = engine.clone();
+    let status_tx = tx.clone();
+    let status_route = warp::path("api")
+        .and(warp::path("status"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(move || {
+            let session = market_session(chrono::Local::now().naive_local());
+            let report = status_engine.health.report(
+                status_engine.market_data.name(),
+                &status_engine.watchlist(),
+                session,
+                status_tx.receiver_count(),
+                std::time::Instant::now(),
+            );
+            warp::reply::json(&report)
+        });
+
+    let session_auth = authenticator.clone();
+    let session_route = warp::path("api")
+        .and(warp::path("session"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(auth::credentials())
+        .and_then(move |token: Option<String>| {
+            let auth = session_auth.clone();
+            async move {
+                auth.issue_session(token.as_deref(), chrono::Utc::now().timestamp())
+                    .map(|session| warp::reply::json(&session))
+                    .map_err(warp::reject::custom)
+            }
+        });
+
+    let openapi_route = warp::path("api")
+        .and(warp::path("openapi.json"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .map(|| warp::reply::json(&openapi::openapi_document()));
+
+    let routes = ws_route.or(arbitrage_route).or(backtest_route).or(strategy_backtest_route).or(walk_forward_route).or(history_route).or(symbols_route).or(opportunities_route)
+        .or(watchlist_get_route).or(watchlist_add_route).or(watchlist_remove_route).or(stream_route).or(poll_route).or(pcp_route).or(surface_route).or(session_route).or(openapi_route)
+        .or(healthz_route).or(readyz_route).or(status_route);
+    auth::gate(authenticator)
+        .and(routes)
+        .recover(auth::handle_rejection)
+        .with(auth::cors(&auth_config))
+}
+
+fn historical_trade_returns() -> Vec<f64> {
+    match load_logged_observations(LOG_FILE) {
+        Ok(history) => run_strategy_backtest(&history, &StrategyBacktestParams::default())
+            .trades
+            .iter()
+            .map(|t| t.return_pct / 100.0)
+            .collect(),
+        Err(e) => {
+            warn!("No trade history available for bootstrap: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+// 503 lets load balancers and orchestrators act on a failed probe
+fn probe_reply(probe: Probe) -> warp::reply::WithStatus<warp::reply::Json> {
+    let status = if probe.ok() { warp::http::StatusCode::OK } else { warp::http::StatusCode::SERVICE_UNAVAILABLE };
+    warp::reply::with_status(warp::reply::json(&probe), status)
+}
+
+// the opportunities built from a result go out first, then the full result
+fn publish_detection(result: DetectorResult, source: DataSource, tx: &broadcast::Sender<FeedMessage>) {
+    publish_opportunities(&result, source, tx);
+    let _ = tx.send(FeedMessage::detection(result, source));
 }
 
-async fn handle_ws_connection(ws: WebSocket, tx: broadcast::Sender<String>) {
+fn publish_opportunities(detection: &impl ToOpportunities, source: DataSource, tx: &broadcast::Sender<FeedMessage>) {
+    for opportunity in detection.to_opportunities(source) {
+        log_opportunity(&opportunity);
+        let _ = tx.send(FeedMessage::Opportunity(Box::new(opportunity)));
+    }
+}
+
+// Clients start subscribed to the full feed and narrow it with JSON commands
+// (see ws_protocol); each command is answered with an ack or error frame.
+// Every frame is a versioned envelope with a per-connection seq, and a client that
+// lags the broadcast channel gets a resync snapshot rather than being dropped.
+// The server pings every HEARTBEAT_INTERVAL_SECS and closes idle connections
+async fn handle_ws_connection(ws: WebSocket, tx: broadcast::Sender<FeedMessage>, engine: Arc<EngineState>) {
     let (mut ws_tx, mut ws_rx) = ws.split();
     let mut rx = tx.subscribe();
+    let mut subscription = Subscription::default();
+    let mut throttle = Throttle::default();
+    let mut sequencer = Sequencer::default();
+    let mut heartbeat = tokio::time::interval(Duration::from_secs(HEARTBEAT_INTERVAL_SECS));
+    heartbeat.tick().await;
+    let mut last_inbound = tokio::time::Instant::now();
 
-    tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            if ws_tx.send(Message::text(msg)).await.is_err() {
-                break;
+    let snapshot = snapshot_frame("connect", 0, engine.latest_results(), &subscription);
+    if ws_tx.send(Message::text(sequencer.stamp(&snapshot))).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            broadcast = rx.recv() => {
+                let frame = match broadcast {
+                    Ok(message) => {
+                        let wanted = classify_feed_message(&message).is_none_or(|item| subscription.matches(&item));
+                        if !wanted || !throttle.allow(subscription.max_messages_per_sec, std::time::Instant::now()) {
+                            continue;
+                        }
+                        message
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("WebSocket client lagged by {} messages, sending resync snapshot", missed);
+                        snapshot_frame("resync", missed, engine.latest_results(), &subscription)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if ws_tx.send(Message::text(sequencer.stamp(&frame))).await.is_err() {
+                    break;
+                }
+            }
+            inbound = ws_rx.next() => {
+                let Some(Ok(message)) = inbound else {
+                    break;
+                };
+                last_inbound = tokio::time::Instant::now();
+                if message.is_close() {
+                    break;
+                }
+                let Ok(text) = message.to_str() else {
+                    continue;
+                };
+                let reply = match parse_command(text) {
+                    Ok(command) => subscription.apply(command),
+                    Err(error) => error,
+                };
+                if ws_tx.send(Message::text(sequencer.stamp(&reply))).await.is_err() {
+                    break;
+                }
+            }
+            _ = heartbeat.tick() => {
+                if last_inbound.elapsed() > Duration::from_secs(IDLE_TIMEOUT_SECS) {
+                    info!("Closing WebSocket client idle for over {}s", IDLE_TIMEOUT_SECS);
+                    let _ = ws_tx.send(Message::close()).await;
+                    break;
+                }
+                let beat = FeedMessage::Heartbeat(Heartbeat { interval_secs: HEARTBEAT_INTERVAL_SECS, request_id: None });
+                if ws_tx.send(Message::ping(Vec::new())).await.is_err() || ws_tx.send(Message::text(sequencer.stamp(&beat))).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// New symbols must be quotable by the current market data source, so a typo fails
+// here instead of as a fetch error every cycle
+async fn handle_watch(request: WatchlistRequest, engine: Arc<EngineState>) -> Result<impl warp::Reply, Infallible> {
+    let error = |message: String, status| warp::reply::with_status(warp::reply::json(&serde_json::json!({ "error": message })), status);
+    let symbol = match normalize_symbol(&request.symbol) {
+        Ok(symbol) => symbol,
+        Err(e) => return Ok(error(e, warp::http::StatusCode::BAD_REQUEST)),
+    };
+    if engine.watchlist().contains(&symbol) {
+        return Ok(error(format!("{} is already on the watchlist", symbol), warp::http::StatusCode::CONFLICT));
+    }
+    if let Err(e) = engine.market_data.spot_price(&symbol, Exchange::Nse).await {
+        return Ok(error(format!("cannot quote {}: {}", symbol, e), warp::http::StatusCode::UNPROCESSABLE_ENTITY));
+    }
+    if !engine.watch(&symbol) {
+        return Ok(error(format!("{} is already on the watchlist", symbol), warp::http::StatusCode::CONFLICT));
+    }
+    info!("Added {} to the watchlist", symbol);
+    Ok(warp::reply::with_status(warp::reply::json(&engine.watchlist()), warp::http::StatusCode::CREATED))
+}
+
+// SSE view of the broadcast feed. A fresh client gets a snapshot first; a resuming
+// client gets everything after its Last-Event-ID still in the replay buffer, preceded
+// by a resync snapshot if some of it was already evicted
+fn feed_stream(engine: Arc<EngineState>, last_event_id: Option<u64>) -> impl futures::Stream<Item = Result<warp::sse::Event, serde_json::Error>> {
+    // an id from before a restart replays from the start of this run, which brings a
+    // resync snapshot with it if the buffer has already wrapped
+    let (start_id, opening) = match last_event_id {
+        Some(id) => (engine.feed_replay.resume_from(id), None),
+        None => (engine.feed_replay.latest_id(), Some("connect")),
+    };
+    let mut pending = std::collections::VecDeque::new();
+    if let Some(reason) = opening {
+        pending.push_back(snapshot_event(&engine, reason, 0));
+    }
+
+    futures::stream::unfold((engine, start_id, pending), |(engine, mut last_id, mut pending)| async move {
+        while pending.is_empty() {
+            let replay = engine.feed_replay.wait_since(last_id, Duration::from_secs(MAX_POLL_SECS)).await;
+            if replay.missed > 0 {
+                pending.push_back(snapshot_event(&engine, "resync", replay.missed));
+            }
+            for event in replay.events {
+                last_id = event.id;
+                pending.push_back(warp::sse::Event::default().id(event.id.to_string()).event(event.message.kind()).json_data(event.envelope()));
             }
         }
+        let event = pending.pop_front()?;
+        Some((event, (engine, last_id, pending)))
+    })
+}
+
+// snapshots carry no SSE id so they don't move the client's resume point
+fn snapshot_event(engine: &EngineState, reason: &str, missed: u64) -> Result<warp::sse::Event, serde_json::Error> {
+    let snapshot = snapshot_frame(reason, missed, engine.latest_results(), &Subscription::default());
+    warp::sse::Event::default().event(snapshot.kind()).json_data(Envelope::new(engine.feed_replay.latest_id(), &snapshot))
+}
+
+async fn handle_poll(query: PollQuery, engine: Arc<EngineState>) -> Result<impl warp::Reply, Infallible> {
+    let after = match query.after {
+        Some(after) => engine.feed_replay.resume_from(after),
+        None => engine.feed_replay.latest_id(),
+    };
+    let timeout = Duration::from_secs(query.timeout_secs.unwrap_or(DEFAULT_POLL_SECS).min(MAX_POLL_SECS));
+    let Replay { missed, events } = engine.feed_replay.wait_since(after, timeout).await;
+    let body = PollResponse {
+        last_event_id: events.last().map_or(after, |e| e.id),
+        missed,
+        events: events.iter().map(|e| e.envelope()).collect(),
+    };
+    Ok(warp::reply::json(&body))
+}
+
+fn field_errors_reply(errors: Vec<FieldError>) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "error": "invalid request", "fields": errors })),
+        warp::http::StatusCode::BAD_REQUEST,
+    )
+}
+
+fn spawn_on_rayon<T: Send + 'static>(job: impl FnOnce() -> T + Send + 'static) -> tokio::sync::oneshot::Receiver<T> {
+    let (result_tx, result_rx) = tokio::sync::oneshot::channel();
+    rayon::spawn(move || {
+        let _ = result_tx.send(job());
     });
+    result_rx
+}
 
-    while let Some(result) = ws_rx.next().await {
-        if result.is_err() {
-            break;
+async fn handle_strategy_backtest(params: StrategyBacktestParams) -> Result<impl warp::Reply, Infallible> {
+    let outcome = spawn_on_rayon(move || load_logged_observations(LOG_FILE).map(|history| run_strategy_backtest(&history, &params))).await;
+    Ok(match outcome {
+        Ok(Ok(response)) => warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::OK),
+        Ok(Err(e)) => {
+            error!("Failed to load history for strategy backtest: {}", e);
+            warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "error": e.to_string() })),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            )
         }
+        Err(_) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "backtest aborted" })),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    })
+}
+
+async fn handle_walk_forward(body: serde_json::Value) -> Result<Box<dyn warp::Reply>, Infallible> {
+    let params = match parse_request::<WalkForwardParams>(body) {
+        Ok(params) => params,
+        Err(errors) => return Ok(Box::new(field_errors_reply(errors))),
+    };
+    let outcome = spawn_on_rayon(move || load_logged_observations(LOG_FILE).map(|history| run_walk_forward(&history, &params))).await;
+    Ok(match outcome {
+        Ok(Ok(response)) => Box::new(warp::reply::json(&response)),
+        Ok(Err(e)) => {
+            error!("Failed to load history for walk-forward optimisation: {}", e);
+            Box::new(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "error": e.to_string() })),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+        Err(_) => Box::new(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "optimisation aborted" })),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+    })
+}
+
+async fn handle_backtest(query: BacktestQuery, body: serde_json::Value) -> Result<Box<dyn warp::Reply>, Infallible> {
+    let mut params = match parse_request::<BacktestParams>(body) {
+        Ok(params) => params,
+        Err(errors) => return Ok(Box::new(field_errors_reply(errors))),
+    };
+
+    // the simulation is CPU-bound, so it runs on the rayon pool instead of a tokio worker
+    let total = total_paths(&params);
+    let control = Arc::new(RunControl::default());
+    let guard = CancelOnDrop(control.clone());
+    let result_rx = spawn_on_rayon(move || -> BacktestOutcome {
+        if params.mode == SimulationMode::Bootstrap && params.trade_returns.is_empty() {
+            params.trade_returns = historical_trade_returns();
+        }
+        run_monte_carlo(params, &control)
+    });
+
+    if query.stream {
+        let events = futures::stream::unfold(Some((result_rx, guard)), move |state| async move {
+            let (mut result_rx, guard) = state?;
+            tokio::select! {
+                outcome = &mut result_rx => {
+                    let event = match outcome {
+                        Ok(Ok(response)) => warp::sse::Event::default().event("result").json_data(&response),
+                        Ok(Err(e)) => warp::sse::Event::default().event("error").json_data(serde_json::json!({ "error": e.to_string() })),
+                        Err(_) => warp::sse::Event::default().event("error").json_data(serde_json::json!({ "error": "simulation aborted" })),
+                    };
+                    Some((event, None))
+                }
+                _ = sleep(Duration::from_millis(250)) => {
+                    let progress = serde_json::json!({ "completed": guard.0.completed(), "total": total });
+                    let event = warp::sse::Event::default().event("progress").json_data(progress);
+                    Some((event, Some((result_rx, guard))))
+                }
+            }
+        });
+        return Ok(Box::new(warp::sse::reply(warp::sse::keep_alive().stream(events))));
     }
+
+    let outcome = result_rx.await;
+    drop(guard);
+    Ok(match outcome {
+        Ok(Ok(response)) => Box::new(warp::reply::json(&response)),
+        Ok(Err(e)) => Box::new(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e.to_string() })),
+            warp::http::StatusCode::BAD_REQUEST,
+        )),
+        Err(_) => Box::new(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "simulation aborted" })),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        )),
+    })
 }
 
-async fn handle_arbitrage_check(symbol: String) -> Result<impl warp::Reply, Infallible> {
-    let client = create_nse_client();
-    let spread_history = create_spread_tracker();
+// Serves the engine's latest result; symbols it has not fetched yet, or
+// ?refresh=true, go to the market through the shared client. These off-cycle
+// quotes never enter the spread history, so the trend stats keep one point per
+// cycle, and only watched symbols replace the latest result
+async fn handle_arbitrage_check(symbol: String, query: ArbitrageQuery, engine: Arc<EngineState>) -> Result<impl warp::Reply, Infallible> {
+    let symbol = match normalize_symbol(&symbol) {
+        Ok(symbol) => symbol,
+        Err(e) => return Ok(warp::reply::with_status(warp::reply::json(&serde_json::json!({ "error": e })), warp::http::StatusCode::BAD_REQUEST)),
+    };
+    if !query.refresh && let Some(result) = engine.latest_result(&symbol) {
+        return Ok(warp::reply::with_status(warp::reply::json(&result), warp::http::StatusCode::OK));
+    }
 
-    match check_arbitrage(&client, &symbol, FUTURES_EXPIRY, &spread_history).await {
-        Ok(result) => Ok(warp::reply::json(&result)),
+    match check_arbitrage(&engine.market_data, &symbol, FUTURES_EXPIRY, &engine.spread_history, &engine.trend_config, false).await {
+        Ok(result) => {
+            engine.record_result(&result);
+            Ok(warp::reply::with_status(warp::reply::json(&result), warp::http::StatusCode::OK))
+        }
         Err(e) => {
             error!("Error fetching data for {}: {:?}", symbol, e);
             let response = ArbitrageResponse {
                 opportunity: false,
                 details: format!("Failed to fetch data: {:?}", e),
             };
-            Ok(warp::reply::json(&response))
+            Ok(warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::OK))
         }
     }
 }
 
-async fn check_arbitrage( client: &reqwest::Client, symbol: &str, expiry: &str, spread_history: &SpreadHistory ) -> Result<ArbitrageResult, Box<dyn std::error::Error + Send + Sync>> {
+async fn check_arbitrage( market_data: &MarketDataSource, symbol: &str, expiry: &str, spread_history: &SpreadHistory, trend_config: &TrendConfig, record_history: bool ) -> Result<ArbitrageResult, Box<dyn std::error::Error + Send + Sync>> {
     info!("Fetching data for {}...", symbol);
     
-    let spot = fetch_nse_spot_price(client, symbol).await?;
-    let futures = fetch_nse_futures_price(client, symbol, expiry).await?;
+    let spot = market_data.spot_price(symbol, Exchange::Nse).await?;
+    let futures = market_data.futures_price(symbol, expiry).await?;
 
     let mut result = detect_cash_futures_arbitrage(
         symbol,
@@ -189,7 +994,93 @@ async fn check_arbitrage( client: &reqwest::Client, symbol: &str, expiry: &str,
         THRESHOLD_PERCENTAGE,
     );
 
-    result.spread_trend = calculate_trend(symbol, result.spread_percentage, spread_history);
+    let point = HistoryPoint {
+        timestamp_ms: chrono::Utc::now().timestamp_millis(),
+        spot_price: result.spot_price,
+        futures_price: result.futures_price,
+        spread_percentage: result.spread_percentage,
+    };
+    let trend = if record_history {
+        calculate_trend(symbol, point, spread_history, trend_config)
+    } else {
+        preview_trend(symbol, &point, spread_history, trend_config)
+    };
+    result.spread_trend = trend.trend;
+    result.trend_stats = Some(trend);
+    result.expiry = futures.expiry;
+
+    Ok(result)
+}
+
+async fn check_index_arbitrage( market_data: &MarketDataSource, definition: &IndexDefinition, spots: &HashMap<String, f64>, expiry: &str, today: chrono::NaiveDate, config: &IndexArbitrageConfig ) -> Result<Option<IndexArbitrageResult>, Box<dyn std::error::Error + Send + Sync>> {
+    info!("Fetching index data for {}...", definition.name);
 
+    let spot = market_data.index_spot_price(definition).await?;
+    let futures = market_data.index_futures_price(definition, expiry).await?;
+    let time_to_expiry = time_to_expiry_years(expiry, today).unwrap_or(0.0);
+
+    let mut result = detect_index_arbitrage(definition, spots, spot.ltp, futures.ltp, time_to_expiry, config);
+    if let Some(result) = result.as_mut() {
+        result.expiry = futures.expiry;
+    }
     Ok(result)
-}
\ No newline at end of file
+}
+
+async fn check_cross_exchange( market_data: &MarketDataSource, symbol: &str, config: &CrossExchangeConfig ) -> Result<CrossExchangeResult, Box<dyn std::error::Error + Send + Sync>> {
+    // fetch both legs together so their latencies are comparable
+    let (nse, bse) = tokio::join!(
+        market_data.spot_price(symbol, Exchange::Nse),
+        market_data.spot_price(symbol, Exchange::Bse),
+    );
+
+    Ok(detect_cross_exchange_arbitrage(symbol, &nse?, &bse?, chrono::Utc::now().timestamp(), config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api() -> (impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone, Arc<EngineState>) {
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({
+            "api_keys": [
+                { "name": "dashboard", "key": "read-key-0123456789", "scope": "read_only" },
+                { "name": "desk", "key": "trade-key-0123456789", "scope": "trading" }
+            ],
+        }))
+        .unwrap();
+        let engine = Arc::new(EngineState::new(MarketDataSource::Yahoo(reqwest::Client::new()), create_spread_tracker(10), TrendConfig::default(), &["TCS"]));
+        let (tx, _rx) = broadcast::channel(16);
+        (routes(engine.clone(), tx, config), engine)
+    }
+
+    #[tokio::test]
+    async fn test_encoded_refresh_still_needs_trading() {
+        let (api, engine) = api();
+        engine.record_result(&detect_cash_futures_arbitrage("TCS", 100.0, 101.0, THRESHOLD_PERCENTAGE));
+        let get = |query: &str| warp::test::request().path(&format!("/arbitrage/TCS{}", query)).header(auth::API_KEY_HEADER, "read-key-0123456789");
+
+        assert_eq!(get("").reply(&api).await.status(), 200);
+        assert_eq!(get("?refresh=false").reply(&api).await.status(), 200);
+        for query in ["?refresh=true", "?refresh=tru%65", "?refresh=%74rue", "?x=1&refresh=true"] {
+            assert_eq!(get(query).reply(&api).await.status(), 403, "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trailing_path_does_not_reach_trading_routes() {
+        let (api, _) = api();
+        let post = |path: &str, key: &str| warp::test::request().method("POST").path(path).header(auth::API_KEY_HEADER, key).json(&serde_json::json!({}));
+
+        // warp matches a trailing slash as the route itself, scope check included
+        for path in ["/api/options/pcp", "/api/options/pcp/", "/api/watchlist", "/api/watchlist/"] {
+            assert_eq!(post(path, "read-key-0123456789").reply(&api).await.status(), 403, "{}", path);
+        }
+        for path in ["/api/options/pcp/x", "/api/options/pcp/x/", "/api/options/surface/x", "/api/backtest/strategy/x", "/api/backtest/walk-forward/x"] {
+            assert_eq!(post(path, "read-key-0123456789").reply(&api).await.status(), 404, "{}", path);
+            assert_eq!(post(path, "trade-key-0123456789").reply(&api).await.status(), 404, "{}", path);
+        }
+        let poll = warp::test::request().path("/api/stream/poll/x").header(auth::API_KEY_HEADER, "read-key-0123456789");
+        assert_eq!(poll.reply(&api).await.status(), 404);
+        assert_eq!(post("/api/options/pcp", "trade-key-0123456789").reply(&api).await.status(), 400);
+    }
+}
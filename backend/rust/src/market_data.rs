@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use chrono::NaiveDate;
+use log::info;
+use reqwest::Client;
+use crate::index_arbitrage::IndexDefinition;
+use crate::nse_data_api::{create_nse_client, generate_option_chain, fetch_exchange_spot_price, fetch_index_futures_price, fetch_index_spot_price, fetch_nse_futures_price, Exchange, FuturesPrice, StockPrice};
+use crate::options_arbitrage::OptionContract;
+use crate::opportunity::{DataSource, SYNTHETIC_MARKET, YAHOO_DELAYED};
+use crate::synthetic_market::{SyntheticMarket, SyntheticMarketConfig};
+use crate::volatility_surface::time_to_expiry_years;
+
+pub const MARKET_DATA_SOURCE_ENV: &str = "MARKET_DATA_SOURCE";
+pub const SYNTHETIC_CONFIG_ENV: &str = "SYNTHETIC_MARKET_CONFIG";
+
+// Where the engine gets its quotes: Yahoo over the network, or the in-process
+// simulator for running without network access and for stress scenarios
+pub enum MarketDataSource {
+    Yahoo(Client),
+    Synthetic(Box<Mutex<SyntheticMarket>>),
+}
+
+// MARKET_DATA_SOURCE=synthetic selects the simulator, configured from the JSON
+// file named by SYNTHETIC_MARKET_CONFIG when set
+pub fn market_data_from_env(symbols: &[&str], reference_prices: &HashMap<String, f64>) -> Result<MarketDataSource, Box<dyn std::error::Error + Send + Sync>> {
+    match std::env::var(MARKET_DATA_SOURCE_ENV).as_deref() {
+        Ok("synthetic") => {
+            let config = match std::env::var(SYNTHETIC_CONFIG_ENV) {
+                Ok(path) => serde_json::from_str(&std::fs::read_to_string(&path)?)?,
+                Err(_) => SyntheticMarketConfig::default(),
+            };
+            info!("Using synthetic market data ({} shock scenarios)", config.shocks.len());
+            Ok(MarketDataSource::Synthetic(Box::new(Mutex::new(SyntheticMarket::new(config, symbols, reference_prices)?))))
+        }
+        Ok("yahoo") | Err(_) => Ok(MarketDataSource::Yahoo(create_nse_client())),
+        Ok(other) => Err(format!("unknown {} '{}', expected yahoo or synthetic", MARKET_DATA_SOURCE_ENV, other).into()),
+    }
+}
+
+fn synthetic_quote(symbol: &str, ltp: f64) -> StockPrice {
+    let now = chrono::Local::now();
+    StockPrice {
+        symbol: symbol.to_string(),
+        ltp,
+        timestamp: now.to_rfc3339(),
+        exchange_time: Some(now.timestamp()),
+        latency_ms: 0,
+    }
+}
+
+fn synthetic_futures(symbol: &str, expiry: &str, ltp: f64) -> FuturesPrice {
+    FuturesPrice {
+        symbol: symbol.to_string(),
+        expiry: expiry.to_string(),
+        ltp,
+        timestamp: chrono::Local::now().to_rfc3339(),
+    }
+}
+
+fn years_to(expiry: &str) -> f64 {
+    time_to_expiry_years(expiry, chrono::Local::now().date_naive()).unwrap_or(0.0)
+}
+
+impl MarketDataSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MarketDataSource::Yahoo(_) => "Yahoo Finance",
+            MarketDataSource::Synthetic(_) => "Synthetic",
+        }
+    }
+
+    // what the detectors record as the provenance of opportunities built from these quotes
+    pub fn data_source(&self) -> DataSource {
+        match self {
+            MarketDataSource::Yahoo(_) => YAHOO_DELAYED,
+            MarketDataSource::Synthetic(_) => SYNTHETIC_MARKET,
+        }
+    }
+
+    // called once per engine cycle; only the simulator has a clock to move
+    pub fn advance(&self) {
+        if let MarketDataSource::Synthetic(market) = self {
+            market.lock().unwrap().advance();
+        }
+    }
+
+    // Yahoo carries no NSE option chains, so only the simulator supplies one; its
+    // scans are marked simulated through data_source()
+    pub fn option_chain(&self, symbol: &str, spot: f64, expiries: &[String], risk_free_rate: f64, as_of: NaiveDate) -> Option<Vec<OptionContract>> {
+        match self {
+            MarketDataSource::Yahoo(_) => None,
+            MarketDataSource::Synthetic(_) => Some(generate_option_chain(symbol, spot, expiries, risk_free_rate, as_of)),
+        }
+    }
+
+    pub async fn spot_price(&self, symbol: &str, exchange: Exchange) -> Result<StockPrice, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            MarketDataSource::Yahoo(client) => fetch_exchange_spot_price(client, symbol, exchange).await,
+            MarketDataSource::Synthetic(market) => {
+                let mut market = market.lock().unwrap();
+                let ltp = match exchange {
+                    Exchange::Nse => market.spot(symbol),
+                    Exchange::Bse => market.bse_spot(symbol),
+                };
+                ltp.map(|ltp| synthetic_quote(symbol, ltp)).ok_or_else(|| format!("{} is not simulated", symbol).into())
+            }
+        }
+    }
+
+    pub async fn futures_price(&self, symbol: &str, expiry: &str) -> Result<FuturesPrice, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            MarketDataSource::Yahoo(client) => fetch_nse_futures_price(client, symbol, expiry).await,
+            MarketDataSource::Synthetic(market) => market
+                .lock()
+                .unwrap()
+                .futures(symbol, years_to(expiry))
+                .map(|ltp| synthetic_futures(symbol, expiry, ltp))
+                .ok_or_else(|| format!("{} is not simulated", symbol).into()),
+        }
+    }
+
+    pub async fn index_spot_price(&self, definition: &IndexDefinition) -> Result<StockPrice, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            MarketDataSource::Yahoo(client) => fetch_index_spot_price(client, &definition.name, &definition.yahoo_symbol).await,
+            MarketDataSource::Synthetic(market) => market
+                .lock()
+                .unwrap()
+                .index_spot(definition)
+                .map(|ltp| synthetic_quote(&definition.name, ltp))
+                .ok_or_else(|| format!("no simulated constituents for {}", definition.name).into()),
+        }
+    }
+
+    pub async fn index_futures_price(&self, definition: &IndexDefinition, expiry: &str) -> Result<FuturesPrice, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            MarketDataSource::Yahoo(client) => fetch_index_futures_price(client, &definition.name, &definition.yahoo_symbol, expiry).await,
+            MarketDataSource::Synthetic(market) => market
+                .lock()
+                .unwrap()
+                .index_futures(definition, years_to(expiry))
+                .map(|ltp| synthetic_futures(&definition.name, expiry, ltp))
+                .ok_or_else(|| format!("no simulated constituents for {}", definition.name).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_simulator_supplies_option_chains() {
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 11, 3).unwrap();
+        let expiries = vec!["25-Nov-2025".to_string()];
+
+        let yahoo = MarketDataSource::Yahoo(Client::new());
+        assert!(yahoo.option_chain("TCS", 4000.0, &expiries, 0.065, today).is_none());
+
+        let market = SyntheticMarket::new(SyntheticMarketConfig::default(), &["TCS"], &HashMap::new()).unwrap();
+        let synthetic = MarketDataSource::Synthetic(Box::new(Mutex::new(market)));
+        assert!(!synthetic.option_chain("TCS", 4000.0, &expiries, 0.065, today).unwrap().is_empty());
+        assert!(synthetic.data_source().simulated);
+    }
+}
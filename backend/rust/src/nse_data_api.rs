@@ -1,15 +1,44 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use serde_json::Value;
 use std::time::Duration;
-use log::{warn, info, error};
+use log::{debug, warn, info, error};
 use rand::Rng;
+use chrono::{Datelike, Duration as ChronoDuration, NaiveDate, Weekday};
+use crate::options_arbitrage::{black_scholes_price, OptionContract, OptionType};
+use crate::volatility_surface::{time_to_expiry_years, EXPIRY_DATE_FORMAT};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Exchange {
+    Nse,
+    Bse,
+}
+
+impl Exchange {
+    pub fn yahoo_suffix(&self) -> &'static str {
+        match self {
+            Exchange::Nse => "NS",
+            Exchange::Bse => "BO",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Exchange::Nse => "NSE",
+            Exchange::Bse => "BSE",
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct StockPrice {
     pub symbol: String,
     pub ltp: f64,
     pub timestamp: String,
+    pub exchange_time: Option<i64>, // unix seconds of the last trade as reported by Yahoo
+    pub latency_ms: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,8 +49,8 @@ pub struct FuturesPrice {
     pub timestamp: String,
 }
 
-fn get_yahoo_symbol(symbol: &str) -> String {
-    format!("{}.NS", symbol)
+fn get_yahoo_symbol(symbol: &str, exchange: Exchange) -> String {
+    format!("{}.{}", symbol, exchange.yahoo_suffix())
 }
 
 fn generate_futures_price_from_spot(spot: f64) -> f64 {
@@ -36,6 +65,86 @@ fn generate_futures_price_from_spot(spot: f64) -> f64 {
     }
 }
 
+fn last_weekday_of_month(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    let mut day = NaiveDate::from_ymd_opt(next_year, next_month, 1).unwrap() - ChronoDuration::days(1);
+    while day.weekday() != weekday {
+        day -= ChronoDuration::days(1);
+    }
+    day
+}
+
+// NSE stock derivatives expire on the last Tuesday of the month
+pub fn upcoming_monthly_expiries(as_of: NaiveDate, count: usize) -> Vec<String> {
+    let mut expiries = Vec::with_capacity(count);
+    let (mut year, mut month) = (as_of.year(), as_of.month());
+
+    while expiries.len() < count {
+        let expiry = last_weekday_of_month(year, month, Weekday::Tue);
+        if expiry > as_of {
+            expiries.push(expiry.format(EXPIRY_DATE_FORMAT).to_string());
+        }
+        if month == 12 {
+            year += 1;
+            month = 1;
+        } else {
+            month += 1;
+        }
+    }
+
+    expiries
+}
+
+fn strike_step(spot: f64) -> f64 {
+    let target = spot * 0.02;
+    [1.0, 2.5, 5.0, 10.0, 20.0, 50.0, 100.0, 250.0, 500.0]
+        .into_iter()
+        .find(|step| *step >= target)
+        .unwrap_or(500.0)
+}
+
+fn round_to_tick(price: f64) -> f64 {
+    ((price / 0.05).round() * 0.05).max(0.05)
+}
+
+pub fn generate_option_chain(symbol: &str, spot: f64, expiries: &[String], risk_free_rate: f64, as_of: NaiveDate) -> Vec<OptionContract> {
+    let mut rng = rand::rng();
+    let step = strike_step(spot);
+    let atm = (spot / step).round() * step;
+    let base_vol = rng.random_range(0.18..0.30);
+    let mut chain = Vec::new();
+
+    for expiry in expiries {
+        let Some(t) = time_to_expiry_years(expiry, as_of) else {
+            continue;
+        };
+        let forward = spot * (risk_free_rate * t).exp();
+
+        // now and then quote one strike well off the smile so the scanner has something to find
+        let mispriced_strike = rng.random_bool(0.1).then(|| rng.random_range(-5..=5));
+
+        for i in -5..=5 {
+            let strike = atm + step * i as f64;
+            let k = (strike / forward).ln();
+            let mut iv = base_vol + 0.01 * t * 12.0 - 0.15 * k + 0.6 * k * k + rng.random_range(-0.003..0.003);
+            if mispriced_strike == Some(i) {
+                iv += rng.random_range(0.04..0.08);
+            }
+
+            chain.push(OptionContract {
+                symbol: symbol.to_string(),
+                strike_price: strike,
+                expiry_date: expiry.clone(),
+                call_price: round_to_tick(black_scholes_price(OptionType::Call, spot, strike, risk_free_rate, t, iv)),
+                put_price: round_to_tick(black_scholes_price(OptionType::Put, spot, strike, risk_free_rate, t, iv)),
+            });
+        }
+    }
+
+    debug!("Simulated option chain for {} ({} contracts)", symbol, chain.len());
+    chain
+}
+
 pub fn create_nse_client() -> Client {
     Client::builder()
         .timeout(Duration::from_secs(30))
@@ -45,9 +154,33 @@ pub fn create_nse_client() -> Client {
 }
 
 pub async fn fetch_nse_spot_price( client: &Client, symbol: &str ) -> Result<StockPrice, Box<dyn std::error::Error + Send + Sync>> {
-    
-    let yahoo_symbol = get_yahoo_symbol(symbol);
-    
+    fetch_exchange_spot_price(client, symbol, Exchange::Nse).await
+}
+
+pub async fn fetch_exchange_spot_price( client: &Client, symbol: &str, exchange: Exchange ) -> Result<StockPrice, Box<dyn std::error::Error + Send + Sync>> {
+    fetch_yahoo_quote(client, symbol, &get_yahoo_symbol(symbol, exchange)).await
+}
+
+// Indices are quoted on Yahoo under their own tickers (^NSEI, ^NSEBANK), not with the .NS suffix
+pub async fn fetch_index_spot_price( client: &Client, index: &str, yahoo_symbol: &str ) -> Result<StockPrice, Box<dyn std::error::Error + Send + Sync>> {
+    fetch_yahoo_quote(client, index, yahoo_symbol).await
+}
+
+pub async fn fetch_index_futures_price( client: &Client, index: &str, yahoo_symbol: &str, expiry: &str ) -> Result<FuturesPrice, Box<dyn std::error::Error + Send + Sync>> {
+    let spot = fetch_index_spot_price(client, index, yahoo_symbol).await?;
+    let futures_ltp = generate_futures_price_from_spot(spot.ltp);
+
+    warn!("Simulated futures price for {}: ₹{:.2} (Yahoo doesn't provide futures data)", index, futures_ltp);
+
+    Ok(FuturesPrice {
+        symbol: index.to_string(),
+        expiry: expiry.to_string(),
+        ltp: futures_ltp,
+        timestamp: chrono::Local::now().to_rfc3339(),
+    })
+}
+
+async fn fetch_yahoo_quote( client: &Client, symbol: &str, yahoo_symbol: &str ) -> Result<StockPrice, Box<dyn std::error::Error + Send + Sync>> {
     let url = format!(
         "https://query1.finance.yahoo.com/v8/finance/chart/{}?interval=1d&range=1d",
         yahoo_symbol
@@ -55,6 +188,7 @@ pub async fn fetch_nse_spot_price( client: &Client, symbol: &str ) -> Result<Sto
     
     info!("Fetching Yahoo Finance URL: {}", url);
     
+    let started = std::time::Instant::now();
     let resp = client.get(&url).header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36").send().await.map_err(|e| {
             error!("Network error for {}: {:?}", symbol, e);
             e
@@ -67,6 +201,7 @@ pub async fn fetch_nse_spot_price( client: &Client, symbol: &str ) -> Result<Sto
     }
     
     let text = resp.text().await?;
+    let latency_ms = started.elapsed().as_millis() as u64;
     
     if text.is_empty() {
         error!("Empty response from Yahoo Finance for {}", symbol);
@@ -86,12 +221,16 @@ pub async fn fetch_nse_spot_price( client: &Client, symbol: &str ) -> Result<Sto
             "Yahoo Finance price missing"
         })?;
     
-    info!("✓ Successfully fetched {} spot price: ₹{:.2}", symbol, ltp);
+    let exchange_time = parsed["chart"]["result"][0]["meta"]["regularMarketTime"].as_i64();
+    
+    info!("✓ Successfully fetched {} spot price: ₹{:.2} ({} ms)", yahoo_symbol, ltp, latency_ms);
     
     Ok(StockPrice {
         symbol: symbol.to_string(),
         ltp,
         timestamp: chrono::Local::now().to_rfc3339(),
+        exchange_time,
+        latency_ms,
     })
 }
 
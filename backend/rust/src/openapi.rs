@@ -0,0 +1,357 @@
+use schemars::generate::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+use crate::arbitrage_detector::ArbitrageResult;
+use crate::auth::{required_scope, Scope, Session, API_KEY_HEADER};
+use crate::backtester::{BacktestParams, BacktestResponse};
+use crate::feed::Envelope;
+use crate::health::{Probe, StatusReport};
+use crate::opportunity::Opportunity;
+use crate::options_arbitrage::{PcpRequest, PutCallParityOpportunity};
+use crate::strategy_backtester::{StrategyBacktestParams, StrategyBacktestResponse};
+use crate::validation::FieldError;
+use crate::volatility_surface::VolatilitySurfaceScan;
+use crate::walk_forward::{WalkForwardParams, WalkForwardResponse};
+use crate::ws_protocol::ClientCommand;
+use crate::{
+    ArbitrageQuery, ArbitrageResponse, BacktestQuery, HistoryQuery, HistoryResponse, OpportunitiesQuery, PollQuery, PollResponse,
+    StreamQuery, SurfaceRequest, SymbolState, WatchlistRequest,
+};
+
+// Shape of every non-2xx JSON body; `fields` is only set for validation failures
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+struct ErrorResponse {
+    error: String,
+    fields: Option<Vec<FieldError>>,
+}
+
+// Request bodies are described as they deserialize (defaulted fields optional) and
+// responses as they serialize; both land in the same components/schemas
+struct SpecBuilder {
+    requests: SchemaGenerator,
+    responses: SchemaGenerator,
+    paths: Map<String, Value>,
+}
+
+impl SpecBuilder {
+    fn new() -> Self {
+        SpecBuilder {
+            requests: SchemaSettings::openapi3().for_deserialize().into_generator(),
+            responses: SchemaSettings::openapi3().for_serialize().into_generator(),
+            paths: Map::new(),
+        }
+    }
+
+    fn body<T: JsonSchema>(&mut self) -> Value {
+        json!({ "required": true, "content": { "application/json": { "schema": self.requests.subschema_for::<T>() } } })
+    }
+
+    fn json<T: JsonSchema>(&mut self, description: &str) -> Value {
+        json!({ "description": description, "content": { "application/json": { "schema": self.responses.subschema_for::<T>() } } })
+    }
+
+    fn error(&mut self, description: &str) -> Value {
+        self.json::<ErrorResponse>(description)
+    }
+
+    // each top-level field of a query struct becomes one `in: query` parameter
+    fn query<T: JsonSchema>(&self) -> Vec<Value> {
+        let schema = SchemaSettings::openapi3().with(|s| s.inline_subschemas = true).into_generator().into_root_schema_for::<T>();
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|r| r.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .map(|properties| {
+                properties
+                    .iter()
+                    .map(|(name, schema)| {
+                        let mut parameter = json!({
+                            "name": name,
+                            "in": "query",
+                            "required": required.contains(&name.as_str()),
+                            "schema": schema,
+                        });
+                        if let Some(description) = schema.get("description") {
+                            parameter["description"] = description.clone();
+                        }
+                        parameter
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // adds the scope the auth gate enforces and the errors it can answer with; routes
+    // that require trading set x-required-scope themselves, and a route may describe
+    // its own 403
+    fn operation(&mut self, path: &str, method: &str, mut operation: Value) {
+        match required_scope(path) {
+            Some(scope) => {
+                if operation.get("x-required-scope").is_none() {
+                    operation["x-required-scope"] = json!(scope);
+                }
+                operation["responses"]["401"] = self.error("Missing, invalid or expired credentials");
+                if operation["responses"].get("403").is_none() {
+                    operation["responses"]["403"] = self.error("The credentials lack the required scope");
+                }
+                operation["responses"]["429"] = self.error("The key's rate limit is exhausted; see Retry-After");
+            }
+            None => operation["security"] = json!([]),
+        }
+        let entry = self.paths.entry(path.to_string()).or_insert_with(|| json!({}));
+        entry[method] = operation;
+    }
+
+    fn components(mut self) -> Map<String, Value> {
+        let mut schemas = self.requests.take_definitions(true);
+        for (name, schema) in self.responses.take_definitions(true) {
+            schemas.entry(name).or_insert(schema);
+        }
+        schemas
+    }
+}
+
+fn path_param(name: &str, description: &str) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "description": description, "schema": { "type": "string" } })
+}
+
+fn with_parameters(mut operation: Value, parameters: Vec<Value>) -> Value {
+    operation["parameters"] = Value::Array(parameters);
+    operation
+}
+
+// The document served at /api/openapi.json. Routes are listed by hand; every schema
+// is generated from the Rust type the handler reads or writes
+pub fn openapi_document() -> Value {
+    let mut spec = SpecBuilder::new();
+
+    let mut arbitrage_params = vec![path_param("symbol", "NSE symbol, e.g. RELIANCE")];
+    arbitrage_params.extend(spec.query::<ArbitrageQuery>());
+    let quote = spec.responses.subschema_for::<ArbitrageResult>();
+    let failure = spec.responses.subschema_for::<ArbitrageResponse>();
+    let invalid = spec.error("Malformed symbol");
+    spec.operation("/arbitrage/{symbol}", "get", with_parameters(json!({
+        "summary": "Latest cash-futures result for a symbol",
+        "description": "Served from the engine's latest result unless refresh=true or the symbol has not been fetched yet. A failed fetch returns 200 with opportunity=false and the error in details. Off-cycle fetches do not add to the spread history, and only watched symbols replace the latest result. refresh=true requires the trading scope.",
+        "responses": { "200": { "description": "Result, or a fetch failure", "content": { "application/json": { "schema": { "oneOf": [quote, failure] } } } }, "400": invalid },
+    }), arbitrage_params));
+
+    let body = spec.body::<BacktestParams>();
+    let ok = spec.json::<BacktestResponse>("Simulation result; with stream=true, an SSE stream of progress events ending in a result or error event");
+    let invalid = spec.error("Invalid parameters, with one entry per field");
+    let query = spec.query::<BacktestQuery>();
+    spec.operation("/api/backtest", "post", with_parameters(json!({
+        "summary": "Monte Carlo simulation of a strategy's equity curve",
+        "requestBody": body,
+        "responses": { "200": ok, "400": invalid },
+    }), query));
+
+    let body = spec.body::<StrategyBacktestParams>();
+    let ok = spec.json::<StrategyBacktestResponse>("Trades and equity curve from replaying the logged history");
+    let failed = spec.error("History could not be loaded");
+    spec.operation("/api/backtest/strategy", "post", json!({
+        "summary": "Replay the logged spread history through entry/exit rules",
+        "requestBody": body,
+        "responses": { "200": ok, "500": failed },
+    }));
+
+    let body = spec.body::<WalkForwardParams>();
+    let ok = spec.json::<WalkForwardResponse>("Per-symbol folds, recommended thresholds and overfitting diagnostics");
+    let invalid = spec.error("Invalid parameters, with one entry per field");
+    let failed = spec.error("History could not be loaded");
+    spec.operation("/api/backtest/walk-forward", "post", json!({
+        "summary": "Walk-forward optimisation of entry/exit thresholds",
+        "requestBody": body,
+        "responses": { "200": ok, "400": invalid, "500": failed },
+    }));
+
+    let mut history_params = vec![path_param("symbol", "NSE symbol")];
+    history_params.extend(spec.query::<HistoryQuery>());
+    let ok = spec.json::<HistoryResponse>("One page of history points, oldest first");
+    let missing = spec.error("No history for the symbol");
+    spec.operation("/api/history/{symbol}", "get", with_parameters(json!({
+        "summary": "Spread history for a symbol by time range, paginated back from the newest point",
+        "responses": { "200": ok, "404": missing },
+    }), history_params));
+
+    let ok = spec.json::<Vec<SymbolState>>("Every watched symbol with its latest result, if any");
+    spec.operation("/api/symbols", "get", json!({ "summary": "Watched symbols and their latest results", "responses": { "200": ok } }));
+
+    let ok = spec.json::<Vec<Opportunity>>("Newest first");
+    let query = spec.query::<OpportunitiesQuery>();
+    spec.operation("/api/opportunities", "get", with_parameters(json!({
+        "summary": "Recent opportunities from the feed replay buffer",
+        "responses": { "200": ok },
+    }), query));
+
+    let ok = spec.json::<Vec<String>>("The watchlist");
+    spec.operation("/api/watchlist", "get", json!({ "summary": "Symbols the engine fetches every cycle", "responses": { "200": ok } }));
+    let body = spec.body::<WatchlistRequest>();
+    let created = spec.json::<Vec<String>>("The updated watchlist");
+    let invalid = spec.error("Malformed symbol");
+    let conflict = spec.error("Already watched");
+    let unquotable = spec.error("The market data source cannot quote the symbol");
+    spec.operation("/api/watchlist", "post", json!({
+        "summary": "Add a symbol to the watchlist from the next cycle",
+        "x-required-scope": Scope::Trading,
+        "requestBody": body,
+        "responses": { "201": created, "400": invalid, "409": conflict, "422": unquotable },
+    }));
+    let ok = spec.json::<Vec<String>>("The updated watchlist");
+    let missing = spec.error("Not on the watchlist");
+    spec.operation("/api/watchlist/{symbol}", "delete", with_parameters(json!({
+        "summary": "Remove a symbol from the watchlist",
+        "x-required-scope": Scope::Trading,
+        "responses": { "200": ok, "400": invalid, "404": missing },
+    }), vec![path_param("symbol", "NSE symbol")]));
+
+    let mut stream_params = spec.query::<StreamQuery>();
+    stream_params.push(json!({ "name": "Last-Event-ID", "in": "header", "required": false, "schema": { "type": "integer", "minimum": 0 } }));
+    let envelope = spec.responses.subschema_for::<Envelope>();
+    spec.operation("/api/stream", "get", with_parameters(json!({
+        "summary": "Server-Sent Events feed",
+        "description": "Each event's data is an Envelope, its event name the envelope type and its id the feed id to resume from. A fresh connection starts with a snapshot event.",
+        "responses": { "200": { "description": "text/event-stream of envelopes", "content": { "text/event-stream": { "schema": envelope } } } },
+    }), stream_params));
+
+    let ok = spec.json::<PollResponse>("Envelopes after `after`, or none if the wait timed out");
+    let query = spec.query::<PollQuery>();
+    spec.operation("/api/stream/poll", "get", with_parameters(json!({
+        "summary": "Long-poll the feed",
+        "responses": { "200": ok },
+    }), query));
+
+    let body = spec.body::<PcpRequest>();
+    let ok = spec.json::<PutCallParityOpportunity>("Parity check on the supplied prices; the result is not published to the feed");
+    let invalid = spec.error("Invalid parameters, with one entry per field");
+    spec.operation("/api/options/pcp", "post", json!({
+        "summary": "Put-call parity check for one strike",
+        "x-required-scope": Scope::Trading,
+        "requestBody": body,
+        "responses": { "200": ok, "400": invalid },
+    }));
+
+    let body = spec.body::<SurfaceRequest>();
+    let ok = spec.json::<VolatilitySurfaceScan>("Fitted smiles and detected anomalies");
+    spec.operation("/api/options/surface", "post", json!({
+        "summary": "Fit a volatility surface to an option chain and scan it for anomalies",
+        "requestBody": body,
+        "responses": { "200": ok },
+    }));
+
+    let ok = spec.json::<Session>("A read-only token for one browser session; token is null when authentication is off");
+    let not_a_key = spec.error("Only API keys can open sessions");
+    let unavailable = spec.error("No jwt_secret is configured");
+    spec.operation("/api/session", "post", json!({
+        "summary": "Exchange an API key for a short-lived read-only JWT",
+        "description": "For a dashboard backend that keeps its API key server-side and hands the browser a session token. Tokens expire after 15 minutes.",
+        "security": [{ "apiKey": [] }],
+        "responses": { "200": ok, "403": not_a_key, "503": unavailable },
+    }));
+
+    let command = spec.requests.subschema_for::<ClientCommand>();
+    let envelope = spec.responses.subschema_for::<Envelope>();
+    spec.operation("/ws", "get", json!({
+        "summary": "WebSocket feed",
+        "description": "Upgrade to a WebSocket; browsers pass credentials as the access_token query parameter. The client sends ClientCommand messages; the server sends Envelope messages, starting with a snapshot.",
+        "x-websocket-messages": { "client": command, "server": envelope },
+        "responses": { "101": { "description": "Switching protocols" } },
+    }));
+
+    let ok = spec.json::<Probe>("The fetch loop is making progress");
+    let down = spec.json::<Probe>("The fetch loop has stalled");
+    spec.operation("/healthz", "get", json!({ "summary": "Liveness probe", "responses": { "200": ok, "503": down } }));
+    let ok = spec.json::<Probe>("A fetch cycle completed recently and a provider circuit is not open");
+    let down = spec.json::<Probe>("Not ready, with the reasons");
+    spec.operation("/readyz", "get", json!({ "summary": "Readiness probe", "responses": { "200": ok, "503": down } }));
+    let ok = spec.json::<StatusReport>("Uptime, fetch loop and provider state");
+    spec.operation("/api/status", "get", json!({
+        "summary": "Engine status: uptime, last successful fetch per symbol, provider circuits, market session, feed subscribers and cycle durations",
+        "responses": { "200": ok },
+    }));
+
+    spec.operation("/api/openapi.json", "get", json!({
+        "summary": "This document",
+        "responses": { "200": { "description": "OpenAPI document", "content": { "application/json": {} } } },
+    }));
+
+    let paths = std::mem::take(&mut spec.paths);
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Cash-futures arbitrage engine",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": "http://127.0.0.1:3030" }],
+        "paths": paths,
+        "security": [{ "apiKey": [] }, { "bearer": [] }, { "accessToken": [] }],
+        "components": {
+            "schemas": spec.components(),
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": API_KEY_HEADER },
+                "bearer": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+                "accessToken": { "type": "apiKey", "in": "query", "name": "access_token" },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every $ref in the document must point at a schema it defines
+    fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(r)) = map.get("$ref") {
+                    refs.push(r.clone());
+                }
+                map.values().for_each(|v| collect_refs(v, refs));
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_document_references_resolve() {
+        let document = openapi_document();
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        for name in ["ArbitrageResult", "BacktestParams", "BacktestResponse", "PutCallParityOpportunity", "Envelope", "ClientCommand"] {
+            assert!(schemas.contains_key(name), "missing schema {}", name);
+        }
+
+        let mut refs = Vec::new();
+        collect_refs(&document, &mut refs);
+        assert!(!refs.is_empty());
+        for r in refs {
+            let name = r.strip_prefix("#/components/schemas/").unwrap_or_else(|| panic!("unexpected ref {}", r));
+            assert!(schemas.contains_key(name), "dangling ref {}", r);
+        }
+    }
+
+    #[test]
+    fn test_query_structs_become_parameters() {
+        let document = openapi_document();
+        let parameters = document["paths"]["/api/history/{symbol}"]["get"]["parameters"].as_array().unwrap();
+        let names: Vec<&str> = parameters.iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(names[0], "symbol");
+        for name in ["from", "to", "limit", "offset"] {
+            assert!(names.contains(&name), "missing {}", name);
+        }
+        assert!(parameters.iter().all(|p| p["in"] == "path" || p["required"] == false));
+        assert!(document["paths"]["/api/options/pcp"]["post"]["responses"]["400"].is_object());
+        assert_eq!(document["paths"]["/api/watchlist"]["post"]["x-required-scope"], "trading");
+        assert_eq!(document["paths"]["/api/watchlist"]["get"]["x-required-scope"], "read_only");
+        assert_eq!(document["paths"]["/api/watchlist/{symbol}"]["delete"]["x-required-scope"], "trading");
+        assert_eq!(document["paths"]["/api/options/pcp"]["post"]["x-required-scope"], "trading");
+        assert_eq!(document["paths"]["/api/session"]["post"]["responses"]["403"]["description"], "Only API keys can open sessions");
+        assert!(document["paths"]["/api/openapi.json"]["get"]["security"].as_array().unwrap().is_empty());
+    }
+}
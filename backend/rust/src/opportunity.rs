@@ -0,0 +1,173 @@
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StrategyKind {
+    CashFutures,
+    PutCallParity,
+    VolatilitySurface,
+    IndexBasket,
+    CrossExchange,
+    StatisticalArbitrage,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct Leg {
+    pub instrument: String,
+    pub side: Side,
+    pub quantity: u32,
+    pub price: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct Provenance {
+    pub detector: String,
+    pub data_source: String,
+    pub simulated_inputs: Vec<String>,
+}
+
+// edge and costs are in rupees for the quantities on the legs; net_edge = edge - costs
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct Opportunity {
+    pub id: String,
+    pub kind: StrategyKind,
+    pub symbol: String,
+    pub legs: Vec<Leg>,
+    pub edge: f64,
+    pub edge_percentage: f64,
+    pub costs: f64,
+    pub net_edge: f64,
+    pub confidence: f64,
+    pub expiry: Option<String>,
+    pub provenance: Provenance,
+    pub details: String,
+    pub detected_at: String,
+}
+
+// Where a detector's quotes came from. A simulated source marks all of its
+// market data as simulated in the provenance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataSource {
+    pub name: &'static str,
+    pub simulated: bool,
+}
+
+pub const YAHOO_DELAYED: DataSource = DataSource { name: "Yahoo Finance (15-min delayed)", simulated: false };
+pub const SYNTHETIC_MARKET: DataSource = DataSource { name: "Synthetic market simulator", simulated: true };
+
+pub trait ToOpportunities {
+    fn to_opportunities(&self, source: DataSource) -> Vec<Opportunity>;
+}
+
+static NEXT_OPPORTUNITY_ID: AtomicU64 = AtomicU64::new(1);
+
+pub fn leg(instrument: impl Into<String>, side: Side, quantity: u32, price: f64) -> Leg {
+    Leg {
+        instrument: instrument.into(),
+        side,
+        quantity,
+        price,
+    }
+}
+
+pub fn provenance(detector: &str, source: DataSource, simulated_inputs: &[&str]) -> Provenance {
+    let market_data = source.simulated.then_some("market_data");
+    Provenance {
+        detector: detector.to_string(),
+        data_source: source.name.to_string(),
+        simulated_inputs: market_data.into_iter().chain(simulated_inputs.iter().copied()).map(str::to_string).collect(),
+    }
+}
+
+// Share of the gross edge left after costs, halved when part of the inputs are simulated
+pub fn cost_adjusted_confidence(edge: f64, costs: f64, provenance: &Provenance) -> f64 {
+    let retention = if edge > 0.0 { ((edge - costs) / edge).clamp(0.0, 1.0) } else { 0.0 };
+    if provenance.simulated_inputs.is_empty() {
+        retention
+    } else {
+        retention * 0.5
+    }
+}
+
+// expiry and details start empty; detectors fill them in on the returned value
+pub fn new_opportunity(
+    kind: StrategyKind,
+    symbol: &str,
+    legs: Vec<Leg>,
+    edge: f64,
+    costs: f64,
+    provenance: Provenance,
+) -> Opportunity {
+    let notional: f64 = legs.iter().map(|l| l.price * l.quantity as f64).sum();
+    let now = chrono::Local::now();
+    let sequence = NEXT_OPPORTUNITY_ID.fetch_add(1, Ordering::Relaxed);
+
+    Opportunity {
+        id: format!("{}-{}", now.format("%Y%m%d%H%M%S"), sequence),
+        kind,
+        symbol: symbol.to_string(),
+        edge_percentage: if notional > 0.0 { edge / notional * 100.0 } else { 0.0 },
+        confidence: cost_adjusted_confidence(edge, costs, &provenance),
+        net_edge: edge - costs,
+        legs,
+        edge,
+        costs,
+        expiry: None,
+        provenance,
+        details: String::new(),
+        detected_at: now.to_rfc3339(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_opportunity_fills_derived_fields() {
+        let legs = vec![
+            leg("RELIANCE", Side::Buy, 250, 2850.0),
+            leg("RELIANCE FUT", Side::Sell, 250, 2865.0),
+        ];
+        let opp = new_opportunity(
+            StrategyKind::CashFutures,
+            "RELIANCE",
+            legs,
+            3750.0,
+            750.0,
+            provenance("cash_futures", YAHOO_DELAYED, &[]),
+        );
+
+        assert_eq!(opp.net_edge, 3000.0);
+        assert!((opp.confidence - 0.8).abs() < 1e-9);
+        assert!(opp.edge_percentage > 0.0);
+
+        let other = new_opportunity(StrategyKind::CashFutures, "TCS", Vec::new(), 0.0, 0.0, provenance("cash_futures", YAHOO_DELAYED, &["futures"]));
+        assert_ne!(opp.id, other.id);
+        assert_eq!(other.confidence, 0.0);
+    }
+
+    #[test]
+    fn test_synthetic_source_is_marked_simulated() {
+        let real = provenance("cash_futures", YAHOO_DELAYED, &[]);
+        assert_eq!(real.data_source, "Yahoo Finance (15-min delayed)");
+        assert!(real.simulated_inputs.is_empty());
+
+        let synthetic = provenance("cash_futures", SYNTHETIC_MARKET, &["futures_price"]);
+        assert_eq!(synthetic.data_source, SYNTHETIC_MARKET.name);
+        assert_eq!(synthetic.simulated_inputs, vec!["market_data", "futures_price"]);
+
+        let legs = vec![leg("TCS", Side::Buy, 175, 4000.0)];
+        let opp = new_opportunity(StrategyKind::CashFutures, "TCS", legs, 1000.0, 200.0, synthetic);
+        assert!((opp.confidence - 0.4).abs() < 1e-9);
+    }
+}
@@ -1,6 +1,14 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::opportunity::{leg, new_opportunity, provenance, DataSource, Opportunity, Side, StrategyKind, ToOpportunities};
+use crate::profit_calculator::{calculate_cash_trade_costs, calculate_futures_costs, get_lot_size};
+use crate::validation::{FieldError, FieldErrors, Validate};
+use crate::volatility_surface::time_to_expiry_years;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+const DEFAULT_RISK_FREE_RATE: f64 = 0.05;
+const MAX_TIME_TO_EXPIRY_YEARS: f64 = 5.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct OptionContract {
     pub symbol: String,
     pub strike_price: f64,
@@ -9,12 +17,14 @@ pub struct OptionContract {
     pub put_price: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct PutCallParityOpportunity {
     pub symbol: String,
     pub strike_price: f64,
     pub expiry_date: String,
     pub spot_price: f64,
+    pub call_price: f64,
+    pub put_price: f64,
     pub synthetic_futures_price: f64, // call_price - put_price + strike_price / (1+r*t)
     pub actual_futures_price: f64,
     pub deviation: f64,
@@ -45,9 +55,292 @@ pub fn detect_put_call_parity(
         strike_price: strike,
         expiry_date: "UNKNOWN".to_string(),
         spot_price: spot,
+        call_price,
+        put_price,
         synthetic_futures_price: synthetic_futures,
         actual_futures_price: futures,
         deviation,
         is_opportunity: deviation > 0.5, // 0.5% threshold
     }
-}
\ No newline at end of file
+}
+
+// Body of POST /api/options/pcp. Prices are required; time to expiry comes from
+// time_to_expiry_years or, failing that, expiry_date (DD-Mon-YYYY)
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PcpRequest {
+    pub symbol: Option<String>,
+    pub expiry_date: Option<String>,
+    pub spot: Option<f64>,
+    pub futures: Option<f64>,
+    pub call_price: Option<f64>,
+    pub put_price: Option<f64>,
+    pub strike: Option<f64>,
+    pub risk_free_rate: Option<f64>,
+    pub time_to_expiry_years: Option<f64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PcpInputs {
+    pub symbol: Option<String>,
+    pub expiry_date: Option<String>,
+    pub spot: f64,
+    pub futures: f64,
+    pub call_price: f64,
+    pub put_price: f64,
+    pub strike: f64,
+    pub risk_free_rate: f64,
+    pub time_to_expiry_years: f64,
+}
+
+impl Validate for PcpRequest {
+    type Output = PcpInputs;
+
+    fn validate(self) -> Result<PcpInputs, Vec<FieldError>> {
+        let mut errors = FieldErrors::default();
+        let spot = errors.required("spot", self.spot);
+        let futures = errors.required("futures", self.futures);
+        let call_price = errors.required("call_price", self.call_price);
+        let put_price = errors.required("put_price", self.put_price);
+        let strike = errors.required("strike", self.strike);
+        for (field, value) in [("spot", spot), ("futures", futures), ("strike", strike)] {
+            if let Some(value) = value {
+                errors.positive(field, value);
+            }
+        }
+        // deep out-of-the-money premiums can legitimately be zero
+        for (field, value) in [("call_price", call_price), ("put_price", put_price)] {
+            if let Some(value) = value {
+                errors.check(value.is_finite() && value >= 0.0, field, "must not be negative");
+            }
+        }
+
+        let risk_free_rate = self.risk_free_rate.unwrap_or(DEFAULT_RISK_FREE_RATE);
+        errors.in_range("risk_free_rate", risk_free_rate, -0.05, 0.25);
+
+        let (field, time_to_expiry) = match (self.time_to_expiry_years, &self.expiry_date) {
+            (Some(t), _) => ("time_to_expiry_years", Some(t)),
+            (None, Some(expiry)) => {
+                let t = time_to_expiry_years(expiry, chrono::Local::now().date_naive());
+                errors.check(t.is_some(), "expiry_date", "must be a future date like 27-Nov-2025");
+                ("expiry_date", t)
+            }
+            (None, None) => {
+                errors.add("time_to_expiry_years", "is required unless expiry_date is given");
+                ("time_to_expiry_years", None)
+            }
+        };
+        if let Some(t) = time_to_expiry {
+            errors.check(t.is_finite() && t > 0.0 && t <= MAX_TIME_TO_EXPIRY_YEARS, field, format!("must be more than 0 and at most {} years away", MAX_TIME_TO_EXPIRY_YEARS));
+        }
+        errors.check(self.symbol.as_ref().is_none_or(|s| !s.trim().is_empty()), "symbol", "must not be empty");
+
+        errors.into_result()?;
+        Ok(PcpInputs {
+            symbol: self.symbol,
+            expiry_date: self.expiry_date,
+            spot: spot.unwrap(),
+            futures: futures.unwrap(),
+            call_price: call_price.unwrap(),
+            put_price: put_price.unwrap(),
+            strike: strike.unwrap(),
+            risk_free_rate,
+            time_to_expiry_years: time_to_expiry.unwrap(),
+        })
+    }
+}
+
+impl PcpInputs {
+    pub fn detect(&self) -> PutCallParityOpportunity {
+        let mut opp = detect_put_call_parity(self.spot, self.futures, self.call_price, self.put_price, self.strike, self.risk_free_rate, self.time_to_expiry_years);
+        if let Some(symbol) = &self.symbol {
+            opp.symbol = symbol.clone();
+        }
+        if let Some(expiry) = &self.expiry_date {
+            opp.expiry_date = expiry.clone();
+        }
+        opp
+    }
+}
+impl ToOpportunities for PutCallParityOpportunity {
+    fn to_opportunities(&self, source: DataSource) -> Vec<Opportunity> {
+        let pcp = self;
+        if !pcp.is_opportunity {
+            return Vec::new();
+        }
+
+        // synthetic long futures = long call + short put; trade it against the listed futures
+        let lot_size = get_lot_size(&pcp.symbol);
+        let (synthetic_side, futures_side) = if pcp.synthetic_futures_price > pcp.actual_futures_price {
+            (Side::Sell, Side::Buy)
+        } else {
+            (Side::Buy, Side::Sell)
+        };
+        let opposite = |side: Side| if side == Side::Buy { Side::Sell } else { Side::Buy };
+
+        let legs = vec![
+            leg(format!("{} {:.0} CE", pcp.symbol, pcp.strike_price), synthetic_side, lot_size, pcp.call_price),
+            leg(format!("{} {:.0} PE", pcp.symbol, pcp.strike_price), opposite(synthetic_side), lot_size, pcp.put_price),
+            leg(format!("{} FUT", pcp.symbol), futures_side, lot_size, pcp.actual_futures_price),
+        ];
+
+        let edge = (pcp.synthetic_futures_price - pcp.actual_futures_price).abs() * lot_size as f64;
+        let premium_value = (pcp.call_price + pcp.put_price) * lot_size as f64;
+        let contract_value = pcp.actual_futures_price * lot_size as f64;
+        let costs = calculate_futures_costs(contract_value) + calculate_cash_trade_costs(premium_value, premium_value);
+
+        let mut opportunity = new_opportunity(StrategyKind::PutCallParity, &pcp.symbol, legs, edge, costs, provenance("options_arbitrage", source, &[]));
+        opportunity.expiry = (pcp.expiry_date != "UNKNOWN").then(|| pcp.expiry_date.clone());
+        opportunity.details = format!(
+            "Synthetic futures {:.2} vs listed {:.2} at strike {:.2} ({:.2}% deviation)",
+            pcp.synthetic_futures_price, pcp.actual_futures_price, pcp.strike_price, pcp.deviation
+        );
+        vec![opportunity]
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionType {
+    Call,
+    Put,
+}
+
+fn normal_cdf(x: f64) -> f64 {
+    // Abramowitz & Stegun 7.1.26, good to ~1e-7 which is plenty for IV work
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs() / std::f64::consts::SQRT_2);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-(x * x) / 2.0).exp();
+
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+pub fn black_scholes_price(
+    option_type: OptionType,
+    spot: f64,
+    strike: f64,
+    risk_free_rate: f64,
+    time_to_expiry_years: f64,
+    volatility: f64,
+) -> f64 {
+    let discounted_strike = strike * (-risk_free_rate * time_to_expiry_years).exp();
+
+    if time_to_expiry_years <= 0.0 || volatility <= 0.0 {
+        return match option_type {
+            OptionType::Call => (spot - discounted_strike).max(0.0),
+            OptionType::Put => (discounted_strike - spot).max(0.0),
+        };
+    }
+
+    let sqrt_t = time_to_expiry_years.sqrt();
+    let d1 = ((spot / strike).ln() + (risk_free_rate + 0.5 * volatility * volatility) * time_to_expiry_years) / (volatility * sqrt_t);
+    let d2 = d1 - volatility * sqrt_t;
+
+    match option_type {
+        OptionType::Call => spot * normal_cdf(d1) - discounted_strike * normal_cdf(d2),
+        OptionType::Put => discounted_strike * normal_cdf(-d2) - spot * normal_cdf(-d1),
+    }
+}
+
+pub fn implied_volatility(
+    option_type: OptionType,
+    option_price: f64,
+    spot: f64,
+    strike: f64,
+    risk_free_rate: f64,
+    time_to_expiry_years: f64,
+) -> Option<f64> {
+    if option_price <= 0.0 || spot <= 0.0 || strike <= 0.0 || time_to_expiry_years <= 0.0 {
+        return None;
+    }
+
+    let intrinsic = black_scholes_price(option_type, spot, strike, risk_free_rate, time_to_expiry_years, 0.0);
+    let upper_bound = match option_type {
+        OptionType::Call => spot,
+        OptionType::Put => strike * (-risk_free_rate * time_to_expiry_years).exp(),
+    };
+    if option_price < intrinsic || option_price >= upper_bound {
+        return None;
+    }
+
+    // price is monotonic in vol, so bisection is slow but never diverges
+    let mut low = 1e-4;
+    let mut high = 5.0;
+    for _ in 0..100 {
+        let mid = 0.5 * (low + high);
+        let price = black_scholes_price(option_type, spot, strike, risk_free_rate, time_to_expiry_years, mid);
+        if price > option_price {
+            high = mid;
+        } else {
+            low = mid;
+        }
+        if high - low < 1e-7 {
+            break;
+        }
+    }
+
+    Some(0.5 * (low + high))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opportunity::YAHOO_DELAYED;
+
+    #[test]
+    fn test_black_scholes_put_call_parity() {
+        let call = black_scholes_price(OptionType::Call, 100.0, 105.0, 0.07, 0.25, 0.2);
+        let put = black_scholes_price(OptionType::Put, 100.0, 105.0, 0.07, 0.25, 0.2);
+        let parity = 100.0 - 105.0 * (-0.07_f64 * 0.25).exp();
+        assert!((call - put - parity).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_pcp_request_validation() {
+        let request: PcpRequest = serde_json::from_value(serde_json::json!({
+            "symbol": "RELIANCE", "spot": 2850.0, "futures": 0.0, "call_price": 120.0, "strike": 2850.0, "risk_free_rate": 0.9
+        }))
+        .unwrap();
+        let fields: Vec<String> = request.validate().unwrap_err().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["put_price", "futures", "risk_free_rate", "time_to_expiry_years"]);
+
+        let expiry = (chrono::Local::now().date_naive() + chrono::Duration::days(73)).format(crate::volatility_surface::EXPIRY_DATE_FORMAT).to_string();
+        let request: PcpRequest = serde_json::from_value(serde_json::json!({
+            "symbol": "RELIANCE", "expiry_date": expiry, "spot": 2850.0, "futures": 2865.0, "call_price": 120.0, "put_price": 60.0, "strike": 2850.0
+        }))
+        .unwrap();
+        let inputs = request.validate().unwrap();
+        assert_eq!(inputs.risk_free_rate, DEFAULT_RISK_FREE_RATE);
+        assert!((inputs.time_to_expiry_years - 0.2).abs() < 1e-9);
+        let pcp = inputs.detect();
+        assert_eq!(pcp.symbol, "RELIANCE");
+        assert_eq!(pcp.expiry_date, expiry);
+    }
+
+    #[test]
+    fn test_parity_opportunity_legs() {
+        let mut pcp = detect_put_call_parity(2850.0, 2865.0, 120.0, 60.0, 2850.0, 0.065, 0.08);
+        pcp.symbol = "RELIANCE".to_string();
+        assert!(pcp.is_opportunity);
+
+        let opportunities = pcp.to_opportunities(YAHOO_DELAYED);
+        assert_eq!(opportunities.len(), 1);
+        assert_eq!(opportunities[0].kind, StrategyKind::PutCallParity);
+        // synthetic 2910 is rich against 2865 futures: sell call, buy put, buy futures
+        let sides: Vec<Side> = opportunities[0].legs.iter().map(|l| l.side).collect();
+        assert_eq!(sides, vec![Side::Sell, Side::Buy, Side::Buy]);
+    }
+
+    #[test]
+    fn test_implied_volatility_round_trip() {
+        let price = black_scholes_price(OptionType::Put, 2850.0, 2700.0, 0.065, 0.1, 0.27);
+        let iv = implied_volatility(OptionType::Put, price, 2850.0, 2700.0, 0.065, 0.1).unwrap();
+        assert!((iv - 0.27).abs() < 1e-4);
+
+        assert!(implied_volatility(OptionType::Call, 0.0, 2850.0, 2700.0, 0.065, 0.1).is_none());
+    }
+}
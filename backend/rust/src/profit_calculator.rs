@@ -55,6 +55,10 @@ pub fn calculate_profit_metrics(
 
 #[allow(dead_code)]
 pub fn calculate_net_profit( gross_profit: f64, contract_value: f64 ) -> f64 {
+    gross_profit - calculate_futures_costs(contract_value)
+}
+
+pub fn calculate_futures_costs( contract_value: f64 ) -> f64 {
     /*Typical costs:
     - Brokerage: ~0.03% or ₹20 per order
     - STT: 0.025% on futures sell side
@@ -66,9 +70,47 @@ pub fn calculate_net_profit( gross_profit: f64, contract_value: f64 ) -> f64 {
     let exchange_charges = contract_value * 0.00002;
     let gst = brokerage * 0.18;
     
-    let total_costs = brokerage + stt + exchange_charges + gst;
-    
-    gross_profit - total_costs
+    brokerage + stt + exchange_charges + gst
+}
+
+pub fn calculate_cash_trade_costs( buy_value: f64, sell_value: f64 ) -> f64 {
+    /*Typical intraday equity costs:
+    - Brokerage: ~0.03% or ₹20 per order
+    - STT: 0.025% on sell side
+    - Exchange charges: ~0.00325% on turnover (NSE 0.00297%, BSE 0.00375%)
+    - SEBI fees: ₹10 per crore
+    - Stamp duty: 0.003% on buy side
+    - GST: 18% on brokerage and exchange charges*/
+
+    let turnover = buy_value + sell_value;
+    let brokerage = (buy_value * 0.0003).min(20.0) + (sell_value * 0.0003).min(20.0);
+    let stt = sell_value * 0.00025;
+    let exchange_charges = turnover * 0.0000325;
+    let sebi_fees = turnover * 0.000001;
+    let stamp_duty = buy_value * 0.00003;
+    let gst = (brokerage + exchange_charges) * 0.18;
+
+    brokerage + stt + exchange_charges + sebi_fees + stamp_duty + gst
+}
+
+pub fn calculate_options_costs( buy_premium: f64, sell_premium: f64, orders: usize ) -> f64 {
+    /*Typical index/stock options costs, all on premium rather than notional:
+    - Brokerage: flat ₹20 per executed order
+    - STT: 0.1% on sell side premium
+    - Exchange charges: ~0.03503% on premium turnover
+    - SEBI fees: ₹10 per crore
+    - Stamp duty: 0.003% on buy side
+    - GST: 18% on brokerage, exchange charges and SEBI fees*/
+
+    let turnover = buy_premium + sell_premium;
+    let brokerage = 20.0 * orders as f64;
+    let stt = sell_premium * 0.001;
+    let exchange_charges = turnover * 0.0003503;
+    let sebi_fees = turnover * 0.000001;
+    let stamp_duty = buy_premium * 0.00003;
+    let gst = (brokerage + exchange_charges + sebi_fees) * 0.18;
+
+    brokerage + stt + exchange_charges + sebi_fees + stamp_duty + gst
 }
 
 #[cfg(test)]
@@ -97,4 +139,19 @@ mod tests {
         assert!(net < 1000.0);
         assert!(net > 900.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cash_trade_costs() {
+        let costs = calculate_cash_trade_costs(100000.0, 100000.0);
+        // 40 brokerage + 25 STT + 6.5 exchange + 0.2 SEBI + 3 stamp + 8.37 GST
+        assert!((costs - 83.07).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_options_costs() {
+        let costs = calculate_options_costs(10000.0, 12000.0, 2);
+        // 40 brokerage + 12 STT + 7.7066 exchange + 0.022 SEBI + 0.3 stamp + 8.591148 GST
+        assert!((costs - 68.619748).abs() < 1e-6);
+        assert_eq!(calculate_options_costs(0.0, 0.0, 0), 0.0);
+    }
+}
@@ -0,0 +1,513 @@
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use std::collections::HashMap;
+use log::info;
+use crate::profit_calculator::{calculate_cash_trade_costs, get_lot_size};
+use crate::opportunity::{leg, new_opportunity, provenance, DataSource, Opportunity, Side, StrategyKind, ToOpportunities};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StatArbConfig {
+    pub min_observations: usize,
+    pub max_history: usize,
+    pub max_alignment_gap_secs: i64,
+    pub adf_critical_value: f64, // Engle-Granger 5% for two series
+    pub max_half_life_obs: f64,
+    pub z_window: usize,
+    pub entry_z: f64,
+    pub exit_z: f64,
+    pub stop_z: f64,
+    pub recalibrate_every_cycles: usize,
+}
+
+impl Default for StatArbConfig {
+    fn default() -> Self {
+        StatArbConfig {
+            min_observations: 30,
+            max_history: 2000,
+            max_alignment_gap_secs: 120,
+            adf_critical_value: -3.34,
+            max_half_life_obs: 200.0,
+            z_window: 60,
+            entry_z: 2.0,
+            exit_z: 0.5,
+            stop_z: 4.0,
+            recalibrate_every_cycles: 10,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct PricePoint {
+    pub timestamp: i64,
+    pub price: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PairModel {
+    pub symbol_y: String,
+    pub symbol_x: String,
+    pub intercept: f64,
+    pub hedge_ratio: f64,
+    pub adf_statistic: f64,
+    pub half_life_obs: f64,
+    pub sample_interval_secs: f64,
+    pub observations: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SpreadPosition {
+    Flat,
+    LongSpread,
+    ShortSpread,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SignalKind {
+    EnterLong,
+    EnterShort,
+    Exit,
+    StopLoss,
+    Hold,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct StatArbSignal {
+    pub pair: String,
+    pub symbol_y: String,
+    pub symbol_x: String,
+    pub price_y: f64,
+    pub price_x: f64,
+    pub hedge_ratio: f64,
+    pub intercept: f64,
+    pub adf_statistic: f64,
+    pub half_life_obs: f64,
+    pub half_life_minutes: f64,
+    pub spread: f64,
+    pub spread_mean: f64,
+    pub spread_std: f64,
+    pub z_score: f64,
+    pub signal: SignalKind,
+    pub position: SpreadPosition,
+    pub action: String,
+    pub quantity_y: u32,
+    pub quantity_x: u32,
+    pub details: String,
+    pub last_update: String,
+}
+
+// Pairs each y observation with the x observation closest to it in time
+pub fn align_series(y: &[PricePoint], x: &[PricePoint], max_gap_secs: i64) -> Vec<(i64, f64, f64)> {
+    let mut aligned = Vec::new();
+    let mut j = 0;
+
+    for point in y {
+        while j + 1 < x.len() && (x[j + 1].timestamp - point.timestamp).abs() <= (x[j].timestamp - point.timestamp).abs() {
+            j += 1;
+        }
+        if let Some(x_point) = x.get(j)
+            && (point.timestamp - x_point.timestamp).abs() <= max_gap_secs
+        {
+            aligned.push((point.timestamp, point.price, x_point.price));
+        }
+    }
+
+    aligned
+}
+
+pub fn ols(x: &[f64], y: &[f64]) -> Option<(f64, f64)> {
+    let n = x.len() as f64;
+    if x.len() < 2 || x.len() != y.len() {
+        return None;
+    }
+    let mean_x = x.iter().sum::<f64>() / n;
+    let mean_y = y.iter().sum::<f64>() / n;
+    let covariance: f64 = x.iter().zip(y).map(|(a, b)| (a - mean_x) * (b - mean_y)).sum();
+    let variance: f64 = x.iter().map(|a| (a - mean_x).powi(2)).sum();
+    if variance <= f64::EPSILON {
+        return None;
+    }
+    let slope = covariance / variance;
+    Some((mean_y - slope * mean_x, slope))
+}
+
+// Dickey-Fuller t-statistic on regression residuals (no constant, no lags)
+pub fn adf_statistic(residuals: &[f64]) -> Option<f64> {
+    if residuals.len() < 3 {
+        return None;
+    }
+    let lagged = &residuals[..residuals.len() - 1];
+    let diffs: Vec<f64> = residuals.windows(2).map(|w| w[1] - w[0]).collect();
+
+    let sum_sq: f64 = lagged.iter().map(|e| e * e).sum();
+    if sum_sq <= f64::EPSILON {
+        return None;
+    }
+    let gamma = lagged.iter().zip(&diffs).map(|(e, d)| e * d).sum::<f64>() / sum_sq;
+    let rss: f64 = lagged.iter().zip(&diffs).map(|(e, d)| (d - gamma * e).powi(2)).sum();
+    let standard_error = (rss / (diffs.len() - 1) as f64 / sum_sq).sqrt();
+    if standard_error <= f64::EPSILON {
+        return None;
+    }
+
+    Some(gamma / standard_error)
+}
+
+pub fn half_life(spread: &[f64]) -> Option<f64> {
+    if spread.len() < 3 {
+        return None;
+    }
+    let lagged = &spread[..spread.len() - 1];
+    let diffs: Vec<f64> = spread.windows(2).map(|w| w[1] - w[0]).collect();
+    let (_, gamma) = ols(lagged, &diffs)?;
+
+    if gamma >= 0.0 || gamma <= -1.0 {
+        return None;
+    }
+    Some(-std::f64::consts::LN_2 / (1.0 + gamma).ln())
+}
+
+pub fn engle_granger(symbol_y: &str, y: &[PricePoint], symbol_x: &str, x: &[PricePoint], config: &StatArbConfig) -> Option<PairModel> {
+    let aligned = align_series(y, x, config.max_alignment_gap_secs);
+    if aligned.len() < config.min_observations {
+        return None;
+    }
+
+    let log_y: Vec<f64> = aligned.iter().map(|(_, py, _)| py.ln()).collect();
+    let log_x: Vec<f64> = aligned.iter().map(|(_, _, px)| px.ln()).collect();
+    let (intercept, hedge_ratio) = ols(&log_x, &log_y)?;
+
+    let residuals: Vec<f64> = log_y.iter().zip(&log_x).map(|(ly, lx)| ly - intercept - hedge_ratio * lx).collect();
+    let adf = adf_statistic(&residuals)?;
+    let half_life_obs = half_life(&residuals)?;
+
+    let mut intervals: Vec<i64> = aligned.windows(2).map(|w| w[1].0 - w[0].0).collect();
+    intervals.sort_unstable();
+    let sample_interval_secs = intervals.get(intervals.len() / 2).copied().unwrap_or(0) as f64;
+
+    Some(PairModel {
+        symbol_y: symbol_y.to_string(),
+        symbol_x: symbol_x.to_string(),
+        intercept,
+        hedge_ratio,
+        adf_statistic: adf,
+        half_life_obs,
+        sample_interval_secs,
+        observations: aligned.len(),
+    })
+}
+
+pub fn find_cointegrated_pairs(history: &HashMap<String, Vec<PricePoint>>, config: &StatArbConfig) -> Vec<PairModel> {
+    let mut symbols: Vec<&String> = history.keys().collect();
+    symbols.sort();
+
+    let mut pairs = Vec::new();
+    for (i, a) in symbols.iter().enumerate() {
+        for b in &symbols[i + 1..] {
+            // Engle-Granger depends on which leg is the regressand, keep the stronger direction
+            let forward = engle_granger(a, &history[*a], b, &history[*b], config);
+            let backward = engle_granger(b, &history[*b], a, &history[*a], config);
+            let best = match (forward, backward) {
+                (Some(f), Some(r)) => Some(if f.adf_statistic <= r.adf_statistic { f } else { r }),
+                (f, r) => f.or(r),
+            };
+
+            if let Some(model) = best
+                && model.adf_statistic < config.adf_critical_value
+                && model.hedge_ratio > 0.0
+                && model.half_life_obs <= config.max_half_life_obs
+            {
+                pairs.push(model);
+            }
+        }
+    }
+
+    pairs.sort_by(|a, b| a.adf_statistic.total_cmp(&b.adf_statistic));
+    pairs
+}
+
+pub struct StatArbTracker {
+    config: StatArbConfig,
+    history: HashMap<String, Vec<PricePoint>>,
+    pairs: Vec<PairModel>,
+    positions: HashMap<String, SpreadPosition>,
+    cycles_since_calibration: usize,
+}
+
+impl StatArbTracker {
+    pub fn new(config: StatArbConfig) -> Self {
+        StatArbTracker {
+            config,
+            history: HashMap::new(),
+            pairs: Vec::new(),
+            positions: HashMap::new(),
+            cycles_since_calibration: usize::MAX,
+        }
+    }
+
+    pub fn record_price(&mut self, symbol: &str, timestamp: i64, price: f64) {
+        if price <= 0.0 {
+            return;
+        }
+        let series = self.history.entry(symbol.to_string()).or_default();
+        series.push(PricePoint { timestamp, price });
+        if series.len() > self.config.max_history {
+            let excess = series.len() - self.config.max_history;
+            series.drain(..excess);
+        }
+    }
+
+    pub fn recalibrate(&mut self) {
+        self.pairs = find_cointegrated_pairs(&self.history, &self.config);
+        self.cycles_since_calibration = 0;
+
+        let active: Vec<String> = self.pairs.iter().map(pair_name).collect();
+        self.positions.retain(|pair, _| active.contains(pair));
+        info!("Stat-arb calibration found {} cointegrated pairs", self.pairs.len());
+    }
+
+    pub fn on_cycle(&mut self) -> Vec<StatArbSignal> {
+        if self.cycles_since_calibration >= self.config.recalibrate_every_cycles {
+            self.recalibrate();
+        } else {
+            self.cycles_since_calibration += 1;
+        }
+
+        let pairs = self.pairs.clone();
+        pairs.iter().filter_map(|model| self.evaluate_pair(model)).collect()
+    }
+
+    fn evaluate_pair(&mut self, model: &PairModel) -> Option<StatArbSignal> {
+        let aligned = align_series(
+            self.history.get(&model.symbol_y)?,
+            self.history.get(&model.symbol_x)?,
+            self.config.max_alignment_gap_secs,
+        );
+        let window = &aligned[aligned.len().saturating_sub(self.config.z_window)..];
+        if window.len() < 3 {
+            return None;
+        }
+
+        let spreads: Vec<f64> = window
+            .iter()
+            .map(|(_, py, px)| py.ln() - model.intercept - model.hedge_ratio * px.ln())
+            .collect();
+        let mean = spreads.iter().sum::<f64>() / spreads.len() as f64;
+        let std_dev = (spreads.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (spreads.len() - 1) as f64).sqrt();
+        if std_dev <= f64::EPSILON {
+            return None;
+        }
+
+        let spread = *spreads.last()?;
+        let z_score = (spread - mean) / std_dev;
+        let (_, price_y, price_x) = *window.last()?;
+
+        let name = pair_name(model);
+        let previous = self.positions.get(&name).copied().unwrap_or(SpreadPosition::Flat);
+        let (signal, position) = next_signal(previous, z_score, &self.config);
+        self.positions.insert(name.clone(), position);
+
+        let quantity_y = get_lot_size(&model.symbol_y);
+        let quantity_x = (model.hedge_ratio * price_y * quantity_y as f64 / price_x).round().max(1.0) as u32;
+
+        let action = match signal {
+            SignalKind::EnterLong => format!("BUY {} {}, SELL {} {}", quantity_y, model.symbol_y, quantity_x, model.symbol_x),
+            SignalKind::EnterShort => format!("SELL {} {}, BUY {} {}", quantity_y, model.symbol_y, quantity_x, model.symbol_x),
+            SignalKind::Exit | SignalKind::StopLoss => format!("CLOSE {}", name),
+            SignalKind::Hold => "HOLD".to_string(),
+        };
+
+        let half_life_minutes = model.half_life_obs * model.sample_interval_secs / 60.0;
+        let details = format!(
+            "{} spread z-score {:.2} (hedge ratio {:.3}, ADF {:.2}, half-life ~{:.0} min): {:?}",
+            name, z_score, model.hedge_ratio, model.adf_statistic, half_life_minutes, signal
+        );
+
+        Some(StatArbSignal {
+            pair: name,
+            symbol_y: model.symbol_y.clone(),
+            symbol_x: model.symbol_x.clone(),
+            price_y,
+            price_x,
+            hedge_ratio: model.hedge_ratio,
+            intercept: model.intercept,
+            adf_statistic: model.adf_statistic,
+            half_life_obs: model.half_life_obs,
+            half_life_minutes,
+            spread,
+            spread_mean: mean,
+            spread_std: std_dev,
+            z_score,
+            signal,
+            position,
+            action,
+            quantity_y,
+            quantity_x,
+            details,
+            last_update: chrono::Local::now().format("%H:%M:%S").to_string(),
+        })
+    }
+}
+
+impl ToOpportunities for StatArbSignal {
+    fn to_opportunities(&self, source: DataSource) -> Vec<Opportunity> {
+        let (y_side, x_side) = match self.signal {
+            SignalKind::EnterLong => (Side::Buy, Side::Sell),
+            SignalKind::EnterShort => (Side::Sell, Side::Buy),
+            _ => return Vec::new(),
+        };
+
+        let legs = vec![
+            leg(&self.symbol_y, y_side, self.quantity_y, self.price_y),
+            leg(&self.symbol_x, x_side, self.quantity_x, self.price_x),
+        ];
+
+        // full reversion to the mean of the log spread, earned on the y leg notional
+        let notional_y = self.price_y * self.quantity_y as f64;
+        let notional_x = self.price_x * self.quantity_x as f64;
+        let edge = (self.spread - self.spread_mean).abs() * notional_y;
+        let costs = 2.0 * calculate_cash_trade_costs(notional_y.max(notional_x), notional_y.min(notional_x));
+
+        let mut opportunity = new_opportunity(StrategyKind::StatisticalArbitrage, &self.pair, legs, edge, costs, provenance("stat_arbitrage", source, &[]));
+        opportunity.details = self.details.clone();
+        vec![opportunity]
+    }
+}
+
+fn pair_name(model: &PairModel) -> String {
+    format!("{}/{}", model.symbol_y, model.symbol_x)
+}
+
+fn next_signal(position: SpreadPosition, z_score: f64, config: &StatArbConfig) -> (SignalKind, SpreadPosition) {
+    match position {
+        SpreadPosition::Flat if z_score >= config.entry_z && z_score < config.stop_z => (SignalKind::EnterShort, SpreadPosition::ShortSpread),
+        SpreadPosition::Flat if z_score <= -config.entry_z && z_score > -config.stop_z => (SignalKind::EnterLong, SpreadPosition::LongSpread),
+        SpreadPosition::Flat => (SignalKind::Hold, SpreadPosition::Flat),
+        _ if z_score.abs() >= config.stop_z => (SignalKind::StopLoss, SpreadPosition::Flat),
+        SpreadPosition::ShortSpread if z_score <= config.exit_z => (SignalKind::Exit, SpreadPosition::Flat),
+        SpreadPosition::LongSpread if z_score >= -config.exit_z => (SignalKind::Exit, SpreadPosition::Flat),
+        held => (SignalKind::Hold, held),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::{DetectorResult, Envelope, FeedMessage};
+    use crate::opportunity::YAHOO_DELAYED;
+    use crate::ws_protocol::{classify_feed_message, Subscription};
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn cointegrated_history(n: usize, seed: u64) -> HashMap<String, Vec<PricePoint>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut log_x = 7.0_f64;
+        let mut noise = 0.0_f64;
+        let mut x = Vec::new();
+        let mut y = Vec::new();
+
+        for i in 0..n {
+            log_x += rng.random_range(-0.01..0.01);
+            noise = 0.8 * noise + rng.random_range(-0.004..0.004);
+            let timestamp = i as i64 * 40;
+            x.push(PricePoint { timestamp, price: log_x.exp() });
+            y.push(PricePoint { timestamp: timestamp + 2, price: (0.5 + 1.2 * log_x + noise).exp() });
+        }
+
+        HashMap::from([("HDFCBANK".to_string(), y), ("ICICIBANK".to_string(), x)])
+    }
+
+    #[test]
+    fn test_engle_granger_finds_cointegrated_pair() {
+        let history = cointegrated_history(400, 7);
+        let pairs = find_cointegrated_pairs(&history, &StatArbConfig::default());
+
+        assert_eq!(pairs.len(), 1);
+        let model = &pairs[0];
+        let hedge = if model.symbol_y == "HDFCBANK" { model.hedge_ratio } else { 1.0 / model.hedge_ratio };
+        assert!((hedge - 1.2).abs() < 0.1);
+        assert!(model.half_life_obs > 1.0 && model.half_life_obs < 10.0);
+        assert_eq!(model.sample_interval_secs, 40.0);
+    }
+
+    #[test]
+    fn test_independent_walks_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(12);
+        let mut a = 7.0_f64;
+        let mut b = 6.0_f64;
+        let mut history: HashMap<String, Vec<PricePoint>> = HashMap::new();
+        for i in 0..400 {
+            a += rng.random_range(-0.01..0.01);
+            b += rng.random_range(-0.01..0.01);
+            history.entry("TCS".to_string()).or_default().push(PricePoint { timestamp: i * 40, price: a.exp() });
+            history.entry("ITC".to_string()).or_default().push(PricePoint { timestamp: i * 40, price: b.exp() });
+        }
+
+        assert!(find_cointegrated_pairs(&history, &StatArbConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_signal_state_machine() {
+        let config = StatArbConfig::default();
+        assert_eq!(next_signal(SpreadPosition::Flat, 2.5, &config), (SignalKind::EnterShort, SpreadPosition::ShortSpread));
+        assert_eq!(next_signal(SpreadPosition::ShortSpread, 1.0, &config), (SignalKind::Hold, SpreadPosition::ShortSpread));
+        assert_eq!(next_signal(SpreadPosition::ShortSpread, 0.2, &config), (SignalKind::Exit, SpreadPosition::Flat));
+        assert_eq!(next_signal(SpreadPosition::LongSpread, -4.5, &config), (SignalKind::StopLoss, SpreadPosition::Flat));
+        assert_eq!(next_signal(SpreadPosition::Flat, -4.5, &config), (SignalKind::Hold, SpreadPosition::Flat));
+    }
+
+    #[test]
+    fn test_tracker_emits_entry_on_spread_shock() {
+        let history = cointegrated_history(300, 3);
+        let mut tracker = StatArbTracker::new(StatArbConfig::default());
+        for (symbol, series) in &history {
+            for point in series {
+                tracker.record_price(symbol, point.timestamp, point.price);
+            }
+        }
+        assert!(!tracker.on_cycle().is_empty());
+
+        let last_x = history["ICICIBANK"].last().unwrap();
+        let last_y = history["HDFCBANK"].last().unwrap();
+        tracker.record_price("ICICIBANK", last_x.timestamp + 40, last_x.price);
+        tracker.record_price("HDFCBANK", last_y.timestamp + 40, last_y.price * 1.012);
+
+        let signals = tracker.on_cycle();
+        assert_eq!(signals.len(), 1);
+        assert!(matches!(signals[0].signal, SignalKind::EnterShort | SignalKind::EnterLong));
+        assert_eq!(signals[0].to_opportunities(YAHOO_DELAYED)[0].kind, StrategyKind::StatisticalArbitrage);
+    }
+
+    #[test]
+    fn test_exit_signal_reaches_the_feed() {
+        let history = cointegrated_history(300, 3);
+        let mut tracker = StatArbTracker::new(StatArbConfig::default());
+        for (symbol, series) in &history {
+            for point in series {
+                tracker.record_price(symbol, point.timestamp, point.price);
+            }
+        }
+        tracker.on_cycle();
+
+        let last_x = history["ICICIBANK"].last().unwrap();
+        let last_y = history["HDFCBANK"].last().unwrap();
+        tracker.record_price("ICICIBANK", last_x.timestamp + 40, last_x.price);
+        tracker.record_price("HDFCBANK", last_y.timestamp + 40, last_y.price * 1.012);
+        assert!(matches!(tracker.on_cycle()[0].signal, SignalKind::EnterShort | SignalKind::EnterLong));
+
+        tracker.record_price("ICICIBANK", last_x.timestamp + 80, last_x.price);
+        tracker.record_price("HDFCBANK", last_y.timestamp + 80, last_y.price * 0.997);
+        let exit = tracker.on_cycle().remove(0);
+        assert_eq!(exit.signal, SignalKind::Exit);
+        assert!(exit.to_opportunities(YAHOO_DELAYED).is_empty());
+
+        let message = FeedMessage::detection(DetectorResult::StatisticalArbitrage(exit), YAHOO_DELAYED);
+        let subscription = Subscription { opportunities_only: true, ..Subscription::default() };
+        assert!(subscription.matches(&classify_feed_message(&message).unwrap()));
+        let envelope = serde_json::to_value(Envelope::new(1, &message)).unwrap();
+        assert_eq!(envelope["type"], "detection");
+        assert_eq!(envelope["payload"]["detector"], "statistical_arbitrage");
+        assert_eq!(envelope["payload"]["result"]["signal"], "EXIT");
+    }
+}
@@ -0,0 +1,376 @@
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use std::collections::HashMap;
+use chrono::NaiveDateTime;
+use crate::arbitrage_detector::detect_cash_futures_arbitrage;
+use crate::data_logger::LoggedObservation;
+use crate::profit_calculator::{calculate_cash_trade_costs, calculate_futures_costs};
+
+const TRADING_SECONDS_PER_YEAR: f64 = 252.0 * 6.25 * 3600.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct StrategyBacktestParams {
+    pub symbols: Vec<String>, // empty means every symbol in the history
+    pub entry_threshold: f64,
+    pub exit_threshold: f64,
+    pub max_holding_secs: Option<i64>,
+    pub expiry: Option<String>, // positions are closed on the first observation on or after this date
+    pub slippage_bps: f64,
+    pub lots: u32,
+    pub initial_capital: f64,
+}
+
+impl Default for StrategyBacktestParams {
+    fn default() -> Self {
+        StrategyBacktestParams {
+            symbols: Vec::new(),
+            entry_threshold: 0.5,
+            exit_threshold: 0.1,
+            max_holding_secs: None,
+            expiry: None,
+            slippage_bps: 2.0,
+            lots: 1,
+            initial_capital: 1_000_000.0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExitReason {
+    Convergence,
+    Expiry,
+    MaxHolding,
+    EndOfData,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct BacktestTrade {
+    pub symbol: String,
+    pub action: String,
+    pub entry_time: String,
+    pub exit_time: String,
+    pub entry_spot: f64,
+    pub entry_futures: f64,
+    pub exit_spot: f64,
+    pub exit_futures: f64,
+    pub entry_spread_pct: f64,
+    pub exit_spread_pct: f64,
+    pub quantity: u32,
+    pub gross_pnl: f64,
+    pub costs: f64,
+    pub net_pnl: f64,
+    pub return_pct: f64,
+    pub holding_secs: i64,
+    pub exit_reason: ExitReason,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct EquityPoint {
+    pub timestamp: String,
+    pub equity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct StrategyBacktestResponse {
+    pub observations: usize,
+    pub total_trades: usize,
+    pub winning_trades: usize,
+    pub hit_rate: f64,
+    pub total_net_pnl: f64,
+    pub final_equity: f64,
+    pub sharpe_ratio: f64,
+    pub max_drawdown: f64,
+    pub average_holding_secs: f64,
+    pub trades: Vec<BacktestTrade>,
+    pub equity_curve: Vec<EquityPoint>,
+}
+
+struct OpenPosition {
+    action: String,
+    long_spot: bool,
+    entry_time: NaiveDateTime,
+    entry_spot: f64,
+    entry_futures: f64,
+    entry_spread_pct: f64,
+    quantity: u32,
+}
+
+impl OpenPosition {
+    // long spot / short futures profits when the basis narrows, and vice versa
+    fn gross_pnl(&self, spot: f64, futures: f64) -> f64 {
+        let basis_change = (spot - self.entry_spot) - (futures - self.entry_futures);
+        let direction = if self.long_spot { 1.0 } else { -1.0 };
+        direction * basis_change * self.quantity as f64
+    }
+
+    fn exit_fills(&self, spot: f64, futures: f64, slippage: f64) -> (f64, f64) {
+        if self.long_spot {
+            (spot * (1.0 - slippage), futures * (1.0 + slippage))
+        } else {
+            (spot * (1.0 + slippage), futures * (1.0 - slippage))
+        }
+    }
+}
+
+fn spread_percentage(spot: f64, futures: f64) -> f64 {
+    (futures - spot) / spot * 100.0
+}
+
+fn round_trip_costs(position: &OpenPosition, exit_spot: f64, exit_futures: f64) -> f64 {
+    let quantity = position.quantity as f64;
+    let spot_values = (position.entry_spot * quantity, exit_spot * quantity);
+    let (spot_buy, spot_sell) = if position.long_spot { spot_values } else { (spot_values.1, spot_values.0) };
+
+    calculate_cash_trade_costs(spot_buy, spot_sell)
+        + calculate_futures_costs((position.entry_futures + exit_futures) / 2.0 * quantity)
+}
+
+fn close_position(position: OpenPosition, symbol: &str, time: NaiveDateTime, spot: f64, futures: f64, slippage: f64, reason: ExitReason) -> BacktestTrade {
+    let (exit_spot, exit_futures) = position.exit_fills(spot, futures, slippage);
+    let gross_pnl = position.gross_pnl(exit_spot, exit_futures);
+    let costs = round_trip_costs(&position, exit_spot, exit_futures);
+    let net_pnl = gross_pnl - costs;
+    let margin = position.entry_futures * position.quantity as f64 * 0.18 + position.entry_spot * position.quantity as f64;
+
+    BacktestTrade {
+        symbol: symbol.to_string(),
+        action: position.action,
+        entry_time: position.entry_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        exit_time: time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        entry_spot: position.entry_spot,
+        entry_futures: position.entry_futures,
+        exit_spot,
+        exit_futures,
+        entry_spread_pct: position.entry_spread_pct,
+        exit_spread_pct: spread_percentage(spot, futures),
+        quantity: position.quantity,
+        gross_pnl,
+        costs,
+        net_pnl,
+        return_pct: net_pnl / margin * 100.0,
+        holding_secs: (time - position.entry_time).num_seconds(),
+        exit_reason: reason,
+    }
+}
+
+fn sharpe_ratio(equity_curve: &[(NaiveDateTime, f64)]) -> f64 {
+    if equity_curve.len() < 3 {
+        return 0.0;
+    }
+
+    let returns: Vec<f64> = equity_curve.windows(2).map(|w| w[1].1 / w[0].1 - 1.0).collect();
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    if variance <= 0.0 {
+        return 0.0;
+    }
+
+    // annualise by how many sampling intervals fit in a trading year
+    let mut intervals: Vec<i64> = equity_curve.windows(2).map(|w| (w[1].0 - w[0].0).num_seconds()).filter(|s| *s > 0).collect();
+    intervals.sort_unstable();
+    let Some(&median_interval) = intervals.get(intervals.len() / 2) else {
+        return 0.0;
+    };
+    let periods_per_year = TRADING_SECONDS_PER_YEAR / median_interval as f64;
+
+    mean / variance.sqrt() * periods_per_year.sqrt()
+}
+
+fn max_drawdown(equity_curve: &[(NaiveDateTime, f64)]) -> f64 {
+    let mut peak = f64::MIN;
+    let mut max_drawdown = 0.0;
+    for &(_, equity) in equity_curve {
+        peak = peak.max(equity);
+        let drawdown = (peak - equity) / peak;
+        if drawdown > max_drawdown {
+            max_drawdown = drawdown;
+        }
+    }
+    max_drawdown * 100.0
+}
+
+pub fn run_strategy_backtest(observations: &[LoggedObservation], params: &StrategyBacktestParams) -> StrategyBacktestResponse {
+    let mut events: Vec<&LoggedObservation> = observations
+        .iter()
+        .filter(|o| o.spot_price > 0.0 && o.futures_price > 0.0)
+        .filter(|o| params.symbols.is_empty() || params.symbols.contains(&o.symbol))
+        .collect();
+    events.sort_by_key(|o| o.timestamp);
+
+    let slippage = params.slippage_bps / 10_000.0;
+    let expiry = params
+        .expiry
+        .as_deref()
+        .and_then(|e| chrono::NaiveDate::parse_from_str(e, crate::volatility_surface::EXPIRY_DATE_FORMAT).ok());
+
+    let mut positions: HashMap<String, OpenPosition> = HashMap::new();
+    let mut last_prices: HashMap<String, (f64, f64)> = HashMap::new();
+    let mut trades = Vec::new();
+    let mut realized = 0.0;
+    let mut equity_curve: Vec<(NaiveDateTime, f64)> = Vec::with_capacity(events.len());
+
+    for event in &events {
+        let symbol = event.symbol.as_str();
+        let (spot, futures) = (event.spot_price, event.futures_price);
+        last_prices.insert(symbol.to_string(), (spot, futures));
+
+        if let Some(position) = positions.get(symbol) {
+            let current_spread = spread_percentage(spot, futures);
+            let converged = if position.long_spot {
+                current_spread <= params.exit_threshold
+            } else {
+                current_spread >= -params.exit_threshold
+            };
+            let expired = expiry.is_some_and(|e| event.timestamp.date() >= e);
+            let held_too_long = params
+                .max_holding_secs
+                .is_some_and(|max| (event.timestamp - position.entry_time).num_seconds() >= max);
+
+            let reason = if expired {
+                Some(ExitReason::Expiry)
+            } else if converged {
+                Some(ExitReason::Convergence)
+            } else if held_too_long {
+                Some(ExitReason::MaxHolding)
+            } else {
+                None
+            };
+
+            if let Some(reason) = reason {
+                let position = positions.remove(symbol).unwrap();
+                let trade = close_position(position, symbol, event.timestamp, spot, futures, slippage, reason);
+                realized += trade.net_pnl;
+                trades.push(trade);
+            }
+        } else if expiry.is_none_or(|e| event.timestamp.date() < e) {
+            // replay the live detector so the backtest enters exactly where the engine would alert
+            let result = detect_cash_futures_arbitrage(symbol, spot, futures, params.entry_threshold);
+            if result.opportunity {
+                let long_spot = result.action.starts_with("BUY");
+                let (entry_spot, entry_futures) = if long_spot {
+                    (spot * (1.0 + slippage), futures * (1.0 - slippage))
+                } else {
+                    (spot * (1.0 - slippage), futures * (1.0 + slippage))
+                };
+                positions.insert(symbol.to_string(), OpenPosition {
+                    action: result.action,
+                    long_spot,
+                    entry_time: event.timestamp,
+                    entry_spot,
+                    entry_futures,
+                    entry_spread_pct: result.spread_percentage,
+                    quantity: params.lots.max(1) * result.lot_size,
+                });
+            }
+        }
+
+        let unrealized: f64 = positions
+            .iter()
+            .map(|(s, p)| {
+                let (spot, futures) = last_prices[s];
+                p.gross_pnl(spot, futures)
+            })
+            .sum();
+        equity_curve.push((event.timestamp, params.initial_capital + realized + unrealized));
+    }
+
+    if let Some(last) = events.last() {
+        let mut open: Vec<String> = positions.keys().cloned().collect();
+        open.sort();
+        for symbol in open {
+            let position = positions.remove(&symbol).unwrap();
+            let (spot, futures) = last_prices[&symbol];
+            let trade = close_position(position, &symbol, last.timestamp, spot, futures, slippage, ExitReason::EndOfData);
+            realized += trade.net_pnl;
+            trades.push(trade);
+        }
+        equity_curve.push((last.timestamp, params.initial_capital + realized));
+    }
+
+    let winning_trades = trades.iter().filter(|t| t.net_pnl > 0.0).count();
+    let total_net_pnl: f64 = trades.iter().map(|t| t.net_pnl).sum();
+
+    StrategyBacktestResponse {
+        observations: events.len(),
+        total_trades: trades.len(),
+        winning_trades,
+        hit_rate: if trades.is_empty() { 0.0 } else { winning_trades as f64 / trades.len() as f64 * 100.0 },
+        total_net_pnl,
+        final_equity: params.initial_capital + total_net_pnl,
+        sharpe_ratio: sharpe_ratio(&equity_curve),
+        max_drawdown: max_drawdown(&equity_curve),
+        average_holding_secs: if trades.is_empty() {
+            0.0
+        } else {
+            trades.iter().map(|t| t.holding_secs as f64).sum::<f64>() / trades.len() as f64
+        },
+        trades,
+        equity_curve: equity_curve
+            .into_iter()
+            .map(|(timestamp, equity)| EquityPoint {
+                timestamp: timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                equity,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(seconds: i64, spot: f64, futures: f64) -> LoggedObservation {
+        let start = NaiveDateTime::parse_from_str("2026-06-25 09:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        LoggedObservation {
+            timestamp: start + chrono::Duration::seconds(seconds),
+            symbol: "RELIANCE".to_string(),
+            spot_price: spot,
+            futures_price: futures,
+        }
+    }
+
+    #[test]
+    fn test_enters_on_spread_and_exits_on_convergence() {
+        let history = vec![
+            observation(0, 1000.0, 1002.0),
+            observation(40, 1000.0, 1010.0),
+            observation(80, 1001.0, 1008.0),
+            observation(120, 1003.0, 1003.5),
+            observation(160, 1003.0, 1012.0),
+        ];
+        let params = StrategyBacktestParams { slippage_bps: 0.0, ..StrategyBacktestParams::default() };
+        let result = run_strategy_backtest(&history, &params);
+
+        assert_eq!(result.total_trades, 2);
+        let first = &result.trades[0];
+        assert_eq!(first.exit_reason, ExitReason::Convergence);
+        assert_eq!(first.quantity, 250);
+        // basis went from 10 to 0.5 on 250 shares
+        assert!((first.gross_pnl - 2375.0).abs() < 1e-6);
+        assert!(first.net_pnl < first.gross_pnl);
+        assert_eq!(result.trades[1].exit_reason, ExitReason::EndOfData);
+    }
+
+    #[test]
+    fn test_slippage_and_holding_limit() {
+        let history = vec![
+            observation(0, 1000.0, 1010.0),
+            observation(600, 1000.0, 1009.0),
+            observation(1200, 1000.0, 1009.0),
+        ];
+        let params = StrategyBacktestParams {
+            max_holding_secs: Some(900),
+            slippage_bps: 10.0,
+            ..StrategyBacktestParams::default()
+        };
+        let result = run_strategy_backtest(&history, &params);
+
+        assert_eq!(result.total_trades, 1);
+        assert_eq!(result.trades[0].exit_reason, ExitReason::MaxHolding);
+        assert!(result.trades[0].entry_spot > 1000.0);
+        assert!(result.max_drawdown >= 0.0);
+        assert_eq!(result.equity_curve.len(), 4);
+    }
+}
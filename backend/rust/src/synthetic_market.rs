@@ -0,0 +1,323 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::index_arbitrage::{synthetic_index_value, IndexDefinition};
+
+const TRADING_SECONDS_PER_YEAR: f64 = 252.0 * 6.25 * 3600.0;
+const DEFAULT_INITIAL_PRICE: f64 = 1000.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ShockScenario {
+    // one-off jump in spot; futures move with it since the basis is kept
+    Gap {
+        at_step: u64,
+        #[serde(default)]
+        symbols: Vec<String>, // empty means every instrument
+        pct: f64,
+    },
+    // basis widens by extra_basis_pct for the duration, then snaps back
+    BasisBlowout {
+        at_step: u64,
+        duration_steps: u64,
+        #[serde(default)]
+        symbols: Vec<String>,
+        extra_basis_pct: f64,
+    },
+    // basis decays linearly to zero over the duration and stays there, as into expiry
+    ExpiryConvergence { at_step: u64, duration_steps: u64 },
+}
+
+impl ShockScenario {
+    fn applies_to(symbols: &[String], symbol: &str) -> bool {
+        symbols.is_empty() || symbols.iter().any(|s| s == symbol)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SyntheticMarketConfig {
+    pub seed: Option<u64>,
+    pub step_secs: f64, // simulated time per engine cycle
+    pub risk_free_rate: f64,
+    pub drift: f64,
+    pub volatility: f64,
+    pub initial_prices: HashMap<String, f64>,
+    // pairwise correlation of spot returns, unless a full matrix over the watchlist is given
+    pub correlation: f64,
+    pub correlation_matrix: Option<Vec<Vec<f64>>>,
+    // Merton jumps in log price: expected jumps per year, and their mean and spread
+    pub jump_intensity: f64,
+    pub jump_mean: f64,
+    pub jump_std: f64,
+    // the basis deviation from cost of carry is an OU process with this half-life and stationary spread
+    pub basis_half_life_secs: f64,
+    pub basis_std_pct: f64,
+    pub bse_noise_bps: f64,
+    pub shocks: Vec<ShockScenario>,
+}
+
+impl Default for SyntheticMarketConfig {
+    fn default() -> Self {
+        SyntheticMarketConfig {
+            seed: None,
+            step_secs: 10.0,
+            risk_free_rate: 0.065,
+            drift: 0.08,
+            volatility: 0.25,
+            initial_prices: HashMap::new(),
+            correlation: 0.4,
+            correlation_matrix: None,
+            jump_intensity: 2.0,
+            jump_mean: -0.01,
+            jump_std: 0.03,
+            basis_half_life_secs: 600.0,
+            basis_std_pct: 0.25,
+            bse_noise_bps: 3.0,
+            shocks: Vec::new(),
+        }
+    }
+}
+
+pub struct SyntheticMarket {
+    config: SyntheticMarketConfig,
+    symbols: Vec<String>,
+    cholesky: Vec<Vec<f64>>,
+    spots: HashMap<String, f64>,
+    basis_deviation: HashMap<String, f64>, // fraction of spot, for stocks and indices alike
+    step: u64,
+    rng: StdRng,
+}
+
+fn standard_normal(rng: &mut impl Rng) -> f64 {
+    // Box-Muller; 1 - u keeps the log argument away from zero
+    let u1: f64 = 1.0 - rng.random::<f64>();
+    let u2: f64 = rng.random();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+// lower-triangular L with L * L^T = matrix
+pub fn cholesky(matrix: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, Box<dyn std::error::Error + Send + Sync>> {
+    let n = matrix.len();
+    if matrix.iter().any(|row| row.len() != n) {
+        return Err("correlation matrix must be square".into());
+    }
+
+    let mut lower = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| lower[i][k] * lower[j][k]).sum();
+            if i == j {
+                let diagonal = matrix[i][i] - sum;
+                if diagonal <= 0.0 {
+                    return Err("correlation matrix is not positive definite".into());
+                }
+                lower[i][j] = diagonal.sqrt();
+            } else {
+                lower[i][j] = (matrix[i][j] - sum) / lower[j][j];
+            }
+        }
+    }
+    Ok(lower)
+}
+
+impl SyntheticMarket {
+    // prices missing from the config start at `reference_prices`, then at a flat default
+    pub fn new(config: SyntheticMarketConfig, symbols: &[&str], reference_prices: &HashMap<String, f64>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let n = symbols.len();
+        let correlation = match &config.correlation_matrix {
+            Some(matrix) => matrix.clone(),
+            None => (0..n)
+                .map(|i| (0..n).map(|j| if i == j { 1.0 } else { config.correlation }).collect())
+                .collect(),
+        };
+        if correlation.len() != n {
+            return Err(format!("correlation matrix is {}x{} but the watchlist has {} symbols", correlation.len(), correlation.len(), n).into());
+        }
+        let cholesky = cholesky(&correlation)?;
+
+        let spots = symbols
+            .iter()
+            .map(|s| {
+                let price = config.initial_prices.get(*s).or_else(|| reference_prices.get(*s)).copied().unwrap_or(DEFAULT_INITIAL_PRICE);
+                (s.to_string(), price)
+            })
+            .collect();
+        let seed = config.seed.unwrap_or_else(|| rand::rng().random());
+
+        Ok(SyntheticMarket {
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+            basis_deviation: symbols.iter().map(|s| (s.to_string(), 0.0)).collect(),
+            config,
+            cholesky,
+            spots,
+            step: 0,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn step_count(&self) -> u64 {
+        self.step
+    }
+
+    // advances every spot and basis by one step_secs
+    pub fn advance(&mut self) {
+        self.step += 1;
+        let dt = self.config.step_secs / TRADING_SECONDS_PER_YEAR;
+        let (drift, volatility) = (self.config.drift, self.config.volatility);
+
+        let independent: Vec<f64> = (0..self.symbols.len()).map(|_| standard_normal(&mut self.rng)).collect();
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            let correlated: f64 = self.cholesky[i].iter().zip(&independent).map(|(l, z)| l * z).sum();
+            let mut log_return = (drift - 0.5 * volatility * volatility) * dt + volatility * dt.sqrt() * correlated;
+            if self.rng.random::<f64>() < self.config.jump_intensity * dt {
+                log_return += self.config.jump_mean + self.config.jump_std * standard_normal(&mut self.rng);
+            }
+            if let Some(spot) = self.spots.get_mut(symbol) {
+                *spot *= log_return.exp();
+            }
+        }
+
+        // exact OU discretisation around zero deviation
+        let decay = (-std::f64::consts::LN_2 / self.config.basis_half_life_secs.max(1.0) * self.config.step_secs).exp();
+        let noise = self.config.basis_std_pct / 100.0 * (1.0 - decay * decay).sqrt();
+        for deviation in self.basis_deviation.values_mut() {
+            *deviation = *deviation * decay + noise * standard_normal(&mut self.rng);
+        }
+
+        for shock in &self.config.shocks {
+            if let ShockScenario::Gap { at_step, symbols, pct } = shock
+                && *at_step == self.step
+            {
+                for (symbol, spot) in self.spots.iter_mut() {
+                    if ShockScenario::applies_to(symbols, symbol) {
+                        *spot *= 1.0 + pct / 100.0;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn spot(&self, symbol: &str) -> Option<f64> {
+        self.spots.get(symbol).copied()
+    }
+
+    pub fn bse_spot(&mut self, symbol: &str) -> Option<f64> {
+        let spot = self.spot(symbol)?;
+        Some(spot * (1.0 + self.config.bse_noise_bps / 10_000.0 * standard_normal(&mut self.rng)))
+    }
+
+    fn basis(&mut self, symbol: &str, time_to_expiry_years: f64) -> f64 {
+        let deviation = *self.basis_deviation.entry(symbol.to_string()).or_insert(0.0);
+        let mut basis = (self.config.risk_free_rate * time_to_expiry_years.max(0.0)).exp() - 1.0 + deviation;
+
+        for shock in &self.config.shocks {
+            match shock {
+                ShockScenario::BasisBlowout { at_step, duration_steps, symbols, extra_basis_pct } => {
+                    if self.step >= *at_step && self.step < at_step + duration_steps && ShockScenario::applies_to(symbols, symbol) {
+                        basis += extra_basis_pct / 100.0;
+                    }
+                }
+                ShockScenario::ExpiryConvergence { at_step, duration_steps } => {
+                    if self.step >= *at_step {
+                        let elapsed = (self.step - at_step) as f64 / (*duration_steps).max(1) as f64;
+                        basis *= 1.0 - elapsed.min(1.0);
+                    }
+                }
+                ShockScenario::Gap { .. } => {}
+            }
+        }
+        basis
+    }
+
+    pub fn futures(&mut self, symbol: &str, time_to_expiry_years: f64) -> Option<f64> {
+        let spot = self.spot(symbol)?;
+        Some(spot * (1.0 + self.basis(symbol, time_to_expiry_years)))
+    }
+
+    // index level is rebuilt from the simulated constituents so the basket stays consistent
+    pub fn index_spot(&self, definition: &IndexDefinition) -> Option<f64> {
+        synthetic_index_value(definition, &self.spots)
+    }
+
+    pub fn index_futures(&mut self, definition: &IndexDefinition, time_to_expiry_years: f64) -> Option<f64> {
+        let spot = self.index_spot(definition)?;
+        Some(spot * (1.0 + self.basis(&definition.name, time_to_expiry_years)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market(config: SyntheticMarketConfig) -> SyntheticMarket {
+        SyntheticMarket::new(SyntheticMarketConfig { seed: Some(21), ..config }, &["RELIANCE", "TCS", "INFY"], &HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_cholesky_reconstructs_matrix() {
+        let matrix = vec![vec![1.0, 0.6, 0.3], vec![0.6, 1.0, 0.5], vec![0.3, 0.5, 1.0]];
+        let lower = cholesky(&matrix).unwrap();
+        for i in 0..3 {
+            for j in 0..3 {
+                let product: f64 = (0..3).map(|k| lower[i][k] * lower[j][k]).sum();
+                assert!((product - matrix[i][j]).abs() < 1e-12);
+            }
+        }
+        assert!(cholesky(&[vec![1.0, 1.5], vec![1.5, 1.0]]).is_err());
+    }
+
+    #[test]
+    fn test_correlated_returns() {
+        let mut market = market(SyntheticMarketConfig { correlation: 0.9, jump_intensity: 0.0, ..SyntheticMarketConfig::default() });
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        for _ in 0..2000 {
+            let (ra, rb) = (market.spot("RELIANCE").unwrap(), market.spot("TCS").unwrap());
+            market.advance();
+            a.push((market.spot("RELIANCE").unwrap() / ra).ln());
+            b.push((market.spot("TCS").unwrap() / rb).ln());
+        }
+
+        let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+        let (ma, mb) = (mean(&a), mean(&b));
+        let covariance: f64 = a.iter().zip(&b).map(|(x, y)| (x - ma) * (y - mb)).sum();
+        let (va, vb): (f64, f64) = (a.iter().map(|x| (x - ma).powi(2)).sum(), b.iter().map(|y| (y - mb).powi(2)).sum());
+        let correlation = covariance / (va * vb).sqrt();
+        assert!((correlation - 0.9).abs() < 0.05, "correlation {}", correlation);
+    }
+
+    #[test]
+    fn test_basis_reverts_and_shocks_apply() {
+        let config = SyntheticMarketConfig {
+            shocks: vec![
+                ShockScenario::Gap { at_step: 5, symbols: vec!["TCS".to_string()], pct: -10.0 },
+                ShockScenario::BasisBlowout { at_step: 10, duration_steps: 3, symbols: Vec::new(), extra_basis_pct: 2.0 },
+                ShockScenario::ExpiryConvergence { at_step: 20, duration_steps: 5 },
+            ],
+            ..SyntheticMarketConfig::default()
+        };
+        let mut market = market(config);
+        let tte = 30.0 / 365.0;
+
+        for _ in 0..4 {
+            market.advance();
+        }
+        let before_gap = market.spot("TCS").unwrap();
+        market.advance();
+        assert!(market.spot("TCS").unwrap() / before_gap < 0.95);
+
+        while market.step_count() < 10 {
+            market.advance();
+        }
+        let spot = market.spot("RELIANCE").unwrap();
+        assert!(market.futures("RELIANCE", tte).unwrap() / spot - 1.0 > 0.015);
+
+        while market.step_count() < 25 {
+            market.advance();
+        }
+        let spot = market.spot("INFY").unwrap();
+        assert!((market.futures("INFY", tte).unwrap() - spot).abs() < 1e-9);
+    }
+}
@@ -1,75 +1,287 @@
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::sync::Arc;
+use log::info;
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use crate::history_store::{HistoryPoint, HistoryStore};
 
-pub type SpreadHistory = Arc<Mutex<HashMap<String, Vec<f64>>>>;
+pub type SpreadHistory = Arc<HistoryStore>;
 
-pub fn create_spread_tracker() -> SpreadHistory {
-    Arc::new(Mutex::new(HashMap::new()))
+pub const TREND_CONFIG_ENV: &str = "TREND_CONFIG";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Trend {
+    Rising,
+    Falling,
+    #[default]
+    Stable,
 }
 
-pub fn calculate_trend( symbol: &str, current_spread: f64, history: &SpreadHistory ) -> String {
-    let mut history_map = history.lock().unwrap();
-    let spreads = history_map.entry(symbol.to_string()).or_insert_with(Vec::new);
-    
-    spreads.push(current_spread);
-    
-    if spreads.len() > 5 {
-        spreads.remove(0);
+impl fmt::Display for Trend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Trend::Rising => "rising",
+            Trend::Falling => "falling",
+            Trend::Stable => "stable",
+        };
+        f.write_str(name)
     }
+}
 
-    if spreads.len() < 3 {
-        return "stable".to_string();
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TrendConfig {
+    pub window: usize,      // most recent spreads used for the rolling statistics
+    pub min_points: usize,  // fewer than this is always stable
+    pub ewma_span: usize,
+    pub z_threshold: f64,   // latest spread this many deviations from the rolling mean
+    pub t_threshold: f64,   // regression slope this many standard errors from zero
+    pub min_move: f64,      // fitted move across the window, in spread percentage points
+}
+
+impl Default for TrendConfig {
+    fn default() -> Self {
+        TrendConfig {
+            window: 20,
+            min_points: 3,
+            ewma_span: 5,
+            z_threshold: 1.0,
+            t_threshold: 2.0,
+            min_move: 0.05,
+        }
     }
-    
-    let recent_avg = spreads[spreads.len() - 2..].iter().sum::<f64>() / 2.0;
-    let older_avg = spreads[..spreads.len() - 2].iter().sum::<f64>() / (spreads.len() - 2) as f64;
-    
-    if recent_avg > older_avg + 0.05 {
-        "rising".to_string()
+}
+
+impl TrendConfig {
+    pub fn check(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.window < 2 {
+            return Err("window must be at least 2".into());
+        }
+        if self.min_points < 2 || self.min_points > self.window {
+            return Err(format!("min_points must be between 2 and the window ({})", self.window).into());
+        }
+        if self.ewma_span == 0 {
+            return Err("ewma_span must be at least 1".into());
+        }
+        for (name, value) in [("z_threshold", self.z_threshold), ("t_threshold", self.t_threshold), ("min_move", self.min_move)] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("{} must be a non-negative number", name).into());
+            }
+        }
+        Ok(())
     }
-    else if recent_avg < older_avg - 0.05 {
-        "falling".to_string()
+}
+
+// TREND_CONFIG names a JSON file; fields it leaves out keep their defaults
+pub fn trend_config_from_env() -> Result<TrendConfig, Box<dyn std::error::Error + Send + Sync>> {
+    let config: TrendConfig = match std::env::var(TREND_CONFIG_ENV) {
+        Ok(path) => serde_json::from_str(&std::fs::read_to_string(&path)?)?,
+        Err(_) => TrendConfig::default(),
+    };
+    config.check()?;
+    info!("Spread trend over {} points, z {} / t {} / min move {}", config.window, config.z_threshold, config.t_threshold, config.min_move);
+    Ok(config)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub struct TrendStats {
+    pub trend: Trend,
+    pub samples: usize,
+    pub ewma: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub z_score: f64,
+    pub slope: f64, // spread percentage points per observation
+    pub slope_t_stat: f64,
+    pub slope_significant: bool,
+}
+
+pub fn create_spread_tracker(depth: usize) -> SpreadHistory {
+    Arc::new(HistoryStore::new(depth))
+}
+
+// ordinary least squares of the spreads on their index: (slope, t-statistic)
+fn regression_slope(spreads: &[f64]) -> (f64, f64) {
+    let n = spreads.len() as f64;
+    let x_mean = (n - 1.0) / 2.0;
+    let y_mean = spreads.iter().sum::<f64>() / n;
+    let sxx: f64 = (0..spreads.len()).map(|i| (i as f64 - x_mean).powi(2)).sum();
+    let sxy: f64 = spreads.iter().enumerate().map(|(i, y)| (i as f64 - x_mean) * (y - y_mean)).sum();
+    let slope = sxy / sxx;
+
+    if spreads.len() < 3 {
+        return (slope, 0.0);
     }
-    else {
-        "stable".to_string()
+    let sse: f64 = spreads
+        .iter()
+        .enumerate()
+        .map(|(i, y)| (y - (y_mean + slope * (i as f64 - x_mean))).powi(2))
+        .sum();
+    let standard_error = (sse / (n - 2.0)).sqrt() / sxx.sqrt();
+    let t_stat = if standard_error > 0.0 {
+        slope / standard_error
+    } else if slope != 0.0 {
+        // a perfect fit is as significant as it gets
+        f64::INFINITY.copysign(slope)
+    } else {
+        0.0
+    };
+    (slope, t_stat)
+}
+
+pub fn analyze_trend(spreads: &[f64], config: &TrendConfig) -> TrendStats {
+    let window = &spreads[spreads.len().saturating_sub(config.window.max(1))..];
+    let Some(&latest) = window.last() else {
+        return TrendStats::default();
+    };
+
+    let alpha = 2.0 / (config.ewma_span as f64 + 1.0);
+    let ewma = window.iter().skip(1).fold(window[0], |ewma, s| alpha * s + (1.0 - alpha) * ewma);
+    let n = window.len() as f64;
+    let mean = window.iter().sum::<f64>() / n;
+    let std_dev = (window.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n).sqrt();
+    let z_score = if std_dev > 0.0 { (latest - mean) / std_dev } else { 0.0 };
+
+    let mut stats = TrendStats {
+        samples: window.len(),
+        ewma,
+        mean,
+        std_dev,
+        z_score,
+        ..TrendStats::default()
+    };
+    if window.len() < config.min_points.max(2) {
+        return stats;
     }
+
+    let (slope, slope_t_stat) = regression_slope(window);
+    stats.slope = slope;
+    stats.slope_t_stat = slope_t_stat;
+    stats.slope_significant = slope_t_stat.abs() >= config.t_threshold;
+
+    // a trend needs a real move, backed by either a significant slope or a stretched latest value
+    let moved = (slope * (n - 1.0)).abs() >= config.min_move;
+    let confirmed = stats.slope_significant || z_score.abs() >= config.z_threshold;
+    stats.trend = if moved && confirmed && slope > 0.0 && z_score >= 0.0 {
+        Trend::Rising
+    } else if moved && confirmed && slope < 0.0 && z_score <= 0.0 {
+        Trend::Falling
+    } else {
+        Trend::Stable
+    };
+    stats
+}
+
+pub fn calculate_trend( symbol: &str, point: HistoryPoint, history: &SpreadHistory, config: &TrendConfig ) -> TrendStats {
+    history.push(symbol, point);
+    analyze_trend(&history.recent_spreads(symbol, config.window), config)
+}
+
+// Same statistics as calculate_trend, but the point is not added to the history.
+pub fn preview_trend( symbol: &str, point: &HistoryPoint, history: &SpreadHistory, config: &TrendConfig ) -> TrendStats {
+    let mut spreads = history.recent_spreads(symbol, config.window);
+    spreads.push(point.spread_percentage);
+    analyze_trend(&spreads, config)
 }
 
+#[allow(dead_code)]
 pub fn get_spread_change( symbol: &str, history: &SpreadHistory ) -> Option<f64> {
-    let history_map = history.lock().unwrap();
-    if let Some(spreads) = history_map.get(symbol) {
-        if spreads.len() >= 2 {
-            let current = spreads[spreads.len() - 1];
-            let previous = spreads[spreads.len() - 2];
-            return Some(current - previous);
-        }
+    let spreads = history.recent_spreads(symbol, 2);
+    if spreads.len() == 2 {
+        return Some(spreads[1] - spreads[0]);
     }
     None
 }
 
+#[allow(dead_code)]
 pub fn clear_history(history: &SpreadHistory) {
-    let mut history_map = history.lock().unwrap();
-    history_map.clear();
+    history.clear();
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    fn point(spread_percentage: f64) -> HistoryPoint {
+        HistoryPoint {
+            timestamp_ms: 0,
+            spot_price: 100.0,
+            futures_price: 100.0 + spread_percentage,
+            spread_percentage,
+        }
+    }
+
     #[test]
     fn test_trend_calculation() {
-        let tracker = create_spread_tracker();
+        let tracker = create_spread_tracker(100);
+        let config = TrendConfig::default();
+        let trend = |symbol: &str, spread: f64, tracker: &SpreadHistory| calculate_trend(symbol, point(spread), tracker, &config).trend;
         
-        assert_eq!(calculate_trend("TEST", 0.5, &tracker), "stable");
-        assert_eq!(calculate_trend("TEST", 0.52, &tracker), "stable");
+        assert_eq!(trend("TEST", 0.5, &tracker), Trend::Stable);
+        assert_eq!(trend("TEST", 0.52, &tracker), Trend::Stable);
         
-        assert_eq!(calculate_trend("TEST", 0.7, &tracker), "rising");
-        assert_eq!(calculate_trend("TEST", 0.75, &tracker), "rising");
+        assert_eq!(trend("TEST", 0.7, &tracker), Trend::Rising);
+        assert_eq!(trend("TEST", 0.75, &tracker), Trend::Rising);
         
-        let tracker2 = create_spread_tracker();
-        calculate_trend("TEST2", 1.0, &tracker2);
-        calculate_trend("TEST2", 0.95, &tracker2);
-        calculate_trend("TEST2", 0.85, &tracker2);
-        assert_eq!(calculate_trend("TEST2", 0.8, &tracker2), "falling");
+        let tracker2 = create_spread_tracker(100);
+        trend("TEST2", 1.0, &tracker2);
+        trend("TEST2", 0.95, &tracker2);
+        trend("TEST2", 0.85, &tracker2);
+        assert_eq!(trend("TEST2", 0.8, &tracker2), Trend::Falling);
+    }
+
+    #[test]
+    fn test_preview_does_not_record() {
+        let tracker = create_spread_tracker(100);
+        let config = TrendConfig::default();
+        for spread in [0.5, 0.55, 0.6, 0.65] {
+            calculate_trend("TEST", point(spread), &tracker, &config);
+        }
+        let before = tracker.recent_spreads("TEST", 100);
+
+        let preview = preview_trend("TEST", &point(0.7), &tracker, &config);
+        assert_eq!(tracker.recent_spreads("TEST", 100), before);
+        let recorded = calculate_trend("TEST", point(0.7), &tracker, &config);
+        assert_eq!(serde_json::to_value(&preview).unwrap(), serde_json::to_value(&recorded).unwrap());
+    }
+
+    #[test]
+    fn test_noise_without_drift_is_stable() {
+        let spreads = [0.50, 0.53, 0.49, 0.52, 0.50, 0.48, 0.52, 0.51, 0.49, 0.50];
+        let stats = analyze_trend(&spreads, &TrendConfig::default());
+        assert_eq!(stats.trend, Trend::Stable);
+        assert!(!stats.slope_significant);
+        assert_eq!(stats.samples, 10);
+    }
+
+    #[test]
+    fn test_regression_statistics() {
+        let spreads: Vec<f64> = (0..10).map(|i| 0.2 + 0.03 * i as f64 + if i % 2 == 0 { 0.005 } else { -0.005 }).collect();
+        let stats = analyze_trend(&spreads, &TrendConfig::default());
+        assert!((stats.slope - 0.03).abs() < 0.002);
+        assert!(stats.slope_significant && stats.slope_t_stat > 10.0);
+        assert!(stats.z_score > 1.0);
+        assert!(stats.ewma > stats.mean);
+        assert_eq!(stats.trend, Trend::Rising);
+        assert_eq!(Trend::Rising.to_string(), "rising");
+
+        let short_window = TrendConfig { window: 4, ..TrendConfig::default() };
+        assert_eq!(analyze_trend(&spreads, &short_window).samples, 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_config_file_overrides_defaults() {
+        let config: TrendConfig = serde_json::from_str(r#"{"window": 40, "z_threshold": 1.5}"#).unwrap();
+        assert_eq!(config.window, 40);
+        assert_eq!(config.z_threshold, 1.5);
+        assert_eq!(config.min_points, TrendConfig::default().min_points);
+        assert!(config.check().is_ok());
+
+        assert!(TrendConfig { window: 1, ..TrendConfig::default() }.check().is_err());
+        assert!(TrendConfig { min_points: 30, ..TrendConfig::default() }.check().is_err());
+        assert!(TrendConfig { ewma_span: 0, ..TrendConfig::default() }.check().is_err());
+        assert!(TrendConfig { t_threshold: f64::NAN, ..TrendConfig::default() }.check().is_err());
+        assert!(TrendConfig { min_move: -0.1, ..TrendConfig::default() }.check().is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use schemars::JsonSchema;
+
+#[derive(Debug, Serialize, Clone, PartialEq, JsonSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+// Collects every problem with a request so a 400 can list them all at once
+#[derive(Default)]
+pub struct FieldErrors(Vec<FieldError>);
+
+impl FieldErrors {
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.0.push(FieldError { field: field.to_string(), message: message.into() });
+    }
+
+    pub fn check(&mut self, ok: bool, field: &str, message: impl Into<String>) {
+        if !ok {
+            self.add(field, message);
+        }
+    }
+
+    pub fn positive(&mut self, field: &str, value: f64) {
+        self.check(value.is_finite() && value > 0.0, field, "must be a positive number");
+    }
+
+    pub fn in_range(&mut self, field: &str, value: f64, min: f64, max: f64) {
+        self.check(value.is_finite() && value >= min && value <= max, field, format!("must be between {} and {}", min, max));
+    }
+
+    // required fields are Options so that every missing one is reported, not just the first
+    pub fn required<T: Copy>(&mut self, field: &str, value: Option<T>) -> Option<T> {
+        if value.is_none() {
+            self.add(field, "is required");
+        }
+        value
+    }
+
+    pub fn into_result(self) -> Result<(), Vec<FieldError>> {
+        if self.0.is_empty() { Ok(()) } else { Err(self.0) }
+    }
+}
+
+// A request body that checks itself after deserializing, producing the values the
+// handler works with
+pub trait Validate: Sized {
+    type Output;
+
+    fn validate(self) -> Result<Self::Output, Vec<FieldError>>;
+}
+
+pub fn parse_request<T: DeserializeOwned + Validate>(body: serde_json::Value) -> Result<T::Output, Vec<FieldError>> {
+    let request: T = serde_json::from_value(body).map_err(|e| vec![deserialize_error(&e)])?;
+    request.validate()
+}
+
+// serde names the field for missing and unknown fields but not for type errors
+fn deserialize_error(error: &serde_json::Error) -> FieldError {
+    let message = error.to_string();
+    let field = message
+        .split('`')
+        .nth(1)
+        .filter(|_| message.starts_with("missing field") || message.starts_with("unknown field"))
+        .unwrap_or("body");
+    FieldError { field: field.to_string(), message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Order {
+        price: Option<f64>,
+        quantity: u32,
+    }
+
+    impl Validate for Order {
+        type Output = (f64, u32);
+
+        fn validate(self) -> Result<(f64, u32), Vec<FieldError>> {
+            let mut errors = FieldErrors::default();
+            let price = errors.required("price", self.price);
+            if let Some(price) = price {
+                errors.positive("price", price);
+            }
+            errors.check(self.quantity > 0, "quantity", "must be at least 1");
+            errors.into_result()?;
+            Ok((price.unwrap(), self.quantity))
+        }
+    }
+
+    #[test]
+    fn test_every_field_error_is_reported() {
+        let errors = parse_request::<Order>(serde_json::json!({ "quantity": 0 })).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["price", "quantity"]);
+
+        let errors = parse_request::<Order>(serde_json::json!({ "price": -1.0, "quantity": 1 })).unwrap_err();
+        assert_eq!(errors[0].message, "must be a positive number");
+
+        assert_eq!(parse_request::<Order>(serde_json::json!({ "price": 2.5, "quantity": 3 })).unwrap(), (2.5, 3));
+    }
+
+    #[test]
+    fn test_deserialize_errors_name_the_field_when_serde_does() {
+        let missing = parse_request::<Order>(serde_json::json!({ "price": 1.0 })).unwrap_err();
+        assert_eq!(missing[0].field, "quantity");
+        let unknown = parse_request::<Order>(serde_json::json!({ "price": 1.0, "quantity": 1, "qty": 1 })).unwrap_err();
+        assert_eq!(unknown[0].field, "qty");
+        let wrong_type = parse_request::<Order>(serde_json::json!({ "price": "cheap", "quantity": 1 })).unwrap_err();
+        assert_eq!(wrong_type[0].field, "body");
+    }
+}
@@ -0,0 +1,548 @@
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use chrono::NaiveDate;
+use log::warn;
+use crate::options_arbitrage::{black_scholes_price, implied_volatility, OptionContract, OptionType};
+use crate::opportunity::{leg, new_opportunity, provenance, DataSource, Leg, Opportunity, Side, StrategyKind, ToOpportunities};
+use crate::profit_calculator::{calculate_options_costs, get_lot_size};
+
+pub const EXPIRY_DATE_FORMAT: &str = "%d-%b-%Y";
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct SurfaceConfig {
+    pub risk_free_rate: f64,
+    pub iv_deviation_threshold: f64, // absolute vol, 0.03 = 3 vol points
+    pub butterfly_tolerance: f64,    // fraction of spot
+    pub calendar_tolerance: f64,     // total variance units
+}
+
+impl Default for SurfaceConfig {
+    fn default() -> Self {
+        SurfaceConfig {
+            risk_free_rate: 0.065,
+            iv_deviation_threshold: 0.03,
+            butterfly_tolerance: 0.0005,
+            calendar_tolerance: 0.0005,
+        }
+    }
+}
+
+// Raw SVI: w(k) = a + b * (rho * (k - m) + sqrt((k - m)^2 + sigma^2))
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, JsonSchema)]
+pub struct SviParams {
+    pub a: f64,
+    pub b: f64,
+    pub rho: f64,
+    pub m: f64,
+    pub sigma: f64,
+}
+
+impl SviParams {
+    pub fn total_variance(&self, log_moneyness: f64) -> f64 {
+        let x = log_moneyness - self.m;
+        self.a + self.b * (self.rho * x + (x * x + self.sigma * self.sigma).sqrt())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct SmilePoint {
+    pub strike: f64,
+    pub log_moneyness: f64,
+    pub option_type: OptionType,
+    pub price: f64,
+    pub market_iv: f64,
+    pub fitted_iv: Option<f64>,
+    pub deviation: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct VolatilitySmile {
+    pub expiry_date: String,
+    pub time_to_expiry_years: f64,
+    pub forward_price: f64,
+    pub svi: Option<SviParams>,
+    pub fit_rmse: Option<f64>,
+    pub points: Vec<SmilePoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AnomalyKind {
+    IvDeviation,
+    ButterflyArbitrage,
+    CalendarArbitrage,
+}
+
+// edge is the rupee value of the mispricing across the suggested legs
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct SurfaceAnomaly {
+    pub kind: AnomalyKind,
+    pub expiry_date: String,
+    pub strike: f64,
+    pub magnitude: f64,
+    pub edge: f64,
+    pub legs: Vec<Leg>,
+    pub details: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct VolatilitySurfaceScan {
+    pub symbol: String,
+    pub spot_price: f64,
+    pub smiles: Vec<VolatilitySmile>,
+    pub anomalies: Vec<SurfaceAnomaly>,
+    pub has_anomalies: bool,
+    pub last_update: String,
+}
+
+fn option_instrument(symbol: &str, expiry_date: &str, strike: f64, option_type: OptionType) -> String {
+    let suffix = match option_type {
+        OptionType::Call => "CE",
+        OptionType::Put => "PE",
+    };
+    format!("{} {} {:.0} {}", symbol, expiry_date, strike, suffix)
+}
+
+pub fn time_to_expiry_years(expiry_date: &str, as_of: NaiveDate) -> Option<f64> {
+    let expiry = NaiveDate::parse_from_str(expiry_date, EXPIRY_DATE_FORMAT).ok()?;
+    let days = (expiry - as_of).num_days();
+    if days <= 0 {
+        return None;
+    }
+    Some(days as f64 / 365.0)
+}
+
+fn solve_3x3(mut m: [[f64; 4]; 3]) -> Option<[f64; 3]> {
+    for col in 0..3 {
+        let pivot = (col..3).max_by(|&i, &j| m[i][col].abs().total_cmp(&m[j][col].abs()))?;
+        if m[pivot][col].abs() < 1e-14 {
+            return None;
+        }
+        m.swap(col, pivot);
+        let pivot_row = m[col];
+        for (row, values) in m.iter_mut().enumerate() {
+            if row != col {
+                let factor = values[col] / pivot_row[col];
+                for (value, pivot_value) in values.iter_mut().zip(pivot_row.iter()).skip(col) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    Some([m[0][3] / m[0][0], m[1][3] / m[1][1], m[2][3] / m[2][2]])
+}
+
+// For fixed (m, sigma) SVI is linear in (a, b*rho, b), so solve that by least squares
+fn fit_svi_slice(points: &[(f64, f64)], m: f64, sigma: f64) -> Option<(SviParams, f64)> {
+    let mut normal = [[0.0; 4]; 3];
+    for &(k, w) in points {
+        let x = k - m;
+        let basis = [1.0, x, (x * x + sigma * sigma).sqrt()];
+        for i in 0..3 {
+            for j in 0..3 {
+                normal[i][j] += basis[i] * basis[j];
+            }
+            normal[i][3] += basis[i] * w;
+        }
+    }
+
+    let [a, c, b] = solve_3x3(normal)?;
+    if b < 0.0 || c.abs() >= b.max(1e-12) {
+        return None;
+    }
+    let rho = if b > 1e-12 { c / b } else { 0.0 };
+    let params = SviParams { a, b, rho, m, sigma };
+
+    // total variance has to stay positive everywhere
+    if a + b * sigma * (1.0 - rho * rho).sqrt() < 0.0 {
+        return None;
+    }
+
+    let sse = points.iter().map(|&(k, w)| (params.total_variance(k) - w).powi(2)).sum();
+    Some((params, sse))
+}
+
+pub fn fit_svi(points: &[(f64, f64)]) -> Option<SviParams> {
+    if points.len() < 5 {
+        return None;
+    }
+
+    let k_min = points.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let k_max = points.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+
+    let mut best: Option<(SviParams, f64)> = None;
+    let mut m_range = (k_min, k_max);
+    let mut sigma_range = (0.005_f64, 1.0_f64);
+
+    // coarse grid, then one finer pass around the best cell
+    for _ in 0..2 {
+        for i in 0..=20 {
+            let m = m_range.0 + (m_range.1 - m_range.0) * i as f64 / 20.0;
+            for j in 0..=20 {
+                let sigma = sigma_range.0 * (sigma_range.1 / sigma_range.0).powf(j as f64 / 20.0);
+                if let Some((params, sse)) = fit_svi_slice(points, m, sigma)
+                    && best.as_ref().is_none_or(|(_, best_sse)| sse < *best_sse)
+                {
+                    best = Some((params, sse));
+                }
+            }
+        }
+
+        let (params, _) = best?;
+        let m_step = (m_range.1 - m_range.0) / 20.0;
+        m_range = (params.m - m_step, params.m + m_step);
+        sigma_range = ((params.sigma / 1.5).max(1e-4), params.sigma * 1.5);
+    }
+
+    best.map(|(params, _)| params)
+}
+
+fn build_smile(
+    expiry_date: &str,
+    spot: f64,
+    contracts: &[&OptionContract],
+    time_to_expiry: f64,
+    config: &SurfaceConfig,
+) -> VolatilitySmile {
+    let forward = spot * (config.risk_free_rate * time_to_expiry).exp();
+
+    let mut points: Vec<SmilePoint> = contracts
+        .iter()
+        .filter_map(|c| {
+            // out-of-the-money side carries the cleaner vol information
+            let (option_type, price) = if c.strike_price < forward {
+                (OptionType::Put, c.put_price)
+            } else {
+                (OptionType::Call, c.call_price)
+            };
+            let iv = implied_volatility(option_type, price, spot, c.strike_price, config.risk_free_rate, time_to_expiry)?;
+            Some(SmilePoint {
+                strike: c.strike_price,
+                log_moneyness: (c.strike_price / forward).ln(),
+                option_type,
+                price,
+                market_iv: iv,
+                fitted_iv: None,
+                deviation: None,
+            })
+        })
+        .collect();
+    points.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+
+    let variances: Vec<(f64, f64)> = points
+        .iter()
+        .map(|p| (p.log_moneyness, p.market_iv * p.market_iv * time_to_expiry))
+        .collect();
+
+    let mut svi = fit_svi(&variances);
+
+    // one robust pass: a single bad quote drags a least-squares fit towards itself,
+    // so refit without the worst point if it is already beyond the threshold
+    if let Some(params) = svi {
+        let worst = points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i, (p.market_iv - (params.total_variance(p.log_moneyness).max(0.0) / time_to_expiry).sqrt()).abs()))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((index, deviation)) = worst && deviation > config.iv_deviation_threshold {
+            let trimmed: Vec<(f64, f64)> = variances
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != index)
+                .map(|(_, v)| *v)
+                .collect();
+            if let Some(refit) = fit_svi(&trimmed) {
+                svi = Some(refit);
+            }
+        }
+    }
+
+    let mut fit_rmse = None;
+    if let Some(params) = svi {
+        let mut sum_sq = 0.0;
+        for point in points.iter_mut() {
+            let fitted = (params.total_variance(point.log_moneyness).max(0.0) / time_to_expiry).sqrt();
+            point.fitted_iv = Some(fitted);
+            point.deviation = Some(point.market_iv - fitted);
+            sum_sq += (point.market_iv - fitted).powi(2);
+        }
+        fit_rmse = Some((sum_sq / points.len() as f64).sqrt());
+    }
+
+    VolatilitySmile {
+        expiry_date: expiry_date.to_string(),
+        time_to_expiry_years: time_to_expiry,
+        forward_price: forward,
+        svi,
+        fit_rmse,
+        points,
+    }
+}
+
+fn find_butterfly_violations(
+    symbol: &str,
+    expiry_date: &str,
+    spot: f64,
+    contracts: &[&OptionContract],
+    config: &SurfaceConfig,
+) -> Vec<SurfaceAnomaly> {
+    let mut calls: Vec<(f64, f64)> = contracts
+        .iter()
+        .filter(|c| c.call_price > 0.0)
+        .map(|c| (c.strike_price, c.call_price))
+        .collect();
+    calls.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let tolerance = config.butterfly_tolerance * spot;
+    let lot_size = get_lot_size(symbol);
+    let mut anomalies = Vec::new();
+
+    // call prices must be convex in strike, otherwise the butterfly has negative cost
+    for window in calls.windows(3) {
+        let (k1, c1) = window[0];
+        let (k2, c2) = window[1];
+        let (k3, c3) = window[2];
+        if k3 <= k1 {
+            continue;
+        }
+        let lambda = (k3 - k2) / (k3 - k1);
+        let violation = c2 - (lambda * c1 + (1.0 - lambda) * c3);
+        if violation > tolerance {
+            // long the wings in proportion, short the overpriced body
+            let wing_lots = |weight: f64| ((weight * lot_size as f64).round() as u32).max(1);
+            anomalies.push(SurfaceAnomaly {
+                kind: AnomalyKind::ButterflyArbitrage,
+                expiry_date: expiry_date.to_string(),
+                strike: k2,
+                magnitude: violation,
+                edge: violation * lot_size as f64,
+                legs: vec![
+                    leg(option_instrument(symbol, expiry_date, k1, OptionType::Call), Side::Buy, wing_lots(lambda), c1),
+                    leg(option_instrument(symbol, expiry_date, k2, OptionType::Call), Side::Sell, lot_size, c2),
+                    leg(option_instrument(symbol, expiry_date, k3, OptionType::Call), Side::Buy, wing_lots(1.0 - lambda), c3),
+                ],
+                details: format!(
+                    "Call at {:.2} is ₹{:.2} above the {:.2}/{:.2} interpolation",
+                    k2, violation, k1, k3
+                ),
+            });
+        }
+    }
+
+    anomalies
+}
+
+fn find_calendar_violations(symbol: &str, spot: f64, near: &VolatilitySmile, far: &VolatilitySmile, config: &SurfaceConfig) -> Vec<SurfaceAnomaly> {
+    let Some(near_svi) = near.svi else {
+        return Vec::new();
+    };
+    let k_min = near.points.iter().map(|p| p.log_moneyness).fold(f64::INFINITY, f64::min);
+    let k_max = near.points.iter().map(|p| p.log_moneyness).fold(f64::NEG_INFINITY, f64::max);
+    let lot_size = get_lot_size(symbol);
+
+    // total variance at fixed moneyness must not decrease with maturity
+    far.points
+        .iter()
+        .filter(|p| p.log_moneyness >= k_min && p.log_moneyness <= k_max)
+        .filter_map(|p| {
+            let far_variance = p.market_iv * p.market_iv * far.time_to_expiry_years;
+            let near_variance = near_svi.total_variance(p.log_moneyness);
+            let shortfall = near_variance - far_variance;
+            if shortfall <= config.calendar_tolerance {
+                return None;
+            }
+
+            // sell the near option at its fitted vol, buy the cheaper far one
+            let near_vol = (near_variance.max(0.0) / near.time_to_expiry_years).sqrt();
+            let near_price = black_scholes_price(p.option_type, spot, p.strike, config.risk_free_rate, near.time_to_expiry_years, near_vol);
+            Some(SurfaceAnomaly {
+                kind: AnomalyKind::CalendarArbitrage,
+                expiry_date: far.expiry_date.clone(),
+                strike: p.strike,
+                magnitude: shortfall,
+                edge: (near_price - p.price).max(0.0) * lot_size as f64,
+                legs: vec![
+                    leg(option_instrument(symbol, &near.expiry_date, p.strike, p.option_type), Side::Sell, lot_size, near_price),
+                    leg(option_instrument(symbol, &far.expiry_date, p.strike, p.option_type), Side::Buy, lot_size, p.price),
+                ],
+                details: format!(
+                    "Total variance {:.5} at {:.2} is below {:.5} for the earlier {} expiry",
+                    far_variance, p.strike, near_variance, near.expiry_date
+                ),
+            })
+        })
+        .collect()
+}
+
+pub fn scan_volatility_surface(
+    symbol: &str,
+    spot: f64,
+    chain: &[OptionContract],
+    as_of: NaiveDate,
+    config: &SurfaceConfig,
+) -> VolatilitySurfaceScan {
+    let mut expiries: Vec<(String, f64)> = Vec::new();
+    for contract in chain {
+        if expiries.iter().any(|(e, _)| *e == contract.expiry_date) {
+            continue;
+        }
+        match time_to_expiry_years(&contract.expiry_date, as_of) {
+            Some(t) => expiries.push((contract.expiry_date.clone(), t)),
+            None => warn!("Skipping expiry {} for {}: unparseable or already expired", contract.expiry_date, symbol),
+        }
+    }
+    expiries.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut smiles = Vec::new();
+    let mut anomalies = Vec::new();
+    let lot_size = get_lot_size(symbol);
+
+    for (expiry_date, t) in &expiries {
+        let contracts: Vec<&OptionContract> = chain.iter().filter(|c| c.expiry_date == *expiry_date).collect();
+        let smile = build_smile(expiry_date, spot, &contracts, *t, config);
+
+        for point in &smile.points {
+            if let (Some(fitted), Some(deviation)) = (point.fitted_iv, point.deviation)
+                && deviation.abs() > config.iv_deviation_threshold
+            {
+                // rich to the surface gets sold, cheap gets bought
+                let fair_price = black_scholes_price(point.option_type, spot, point.strike, config.risk_free_rate, *t, fitted);
+                let side = if deviation > 0.0 { Side::Sell } else { Side::Buy };
+                anomalies.push(SurfaceAnomaly {
+                    kind: AnomalyKind::IvDeviation,
+                    expiry_date: expiry_date.clone(),
+                    strike: point.strike,
+                    magnitude: deviation,
+                    edge: (point.price - fair_price).abs() * lot_size as f64,
+                    legs: vec![leg(option_instrument(symbol, expiry_date, point.strike, point.option_type), side, lot_size, point.price)],
+                    details: format!(
+                        "IV {:.2}% vs fitted {:.2}% at strike {:.2}",
+                        point.market_iv * 100.0, fitted * 100.0, point.strike
+                    ),
+                });
+            }
+        }
+
+        anomalies.extend(find_butterfly_violations(symbol, expiry_date, spot, &contracts, config));
+        smiles.push(smile);
+    }
+
+    for pair in smiles.windows(2) {
+        anomalies.extend(find_calendar_violations(symbol, spot, &pair[0], &pair[1], config));
+    }
+
+    VolatilitySurfaceScan {
+        symbol: symbol.to_string(),
+        spot_price: spot,
+        has_anomalies: !anomalies.is_empty(),
+        smiles,
+        anomalies,
+        last_update: chrono::Local::now().format("%H:%M:%S").to_string(),
+    }
+}
+
+impl ToOpportunities for VolatilitySurfaceScan {
+    fn to_opportunities(&self, source: DataSource) -> Vec<Opportunity> {
+        self.anomalies
+            .iter()
+            .filter(|anomaly| anomaly.edge > 0.0)
+            .map(|anomaly| {
+                let bought: f64 = anomaly.legs.iter().filter(|l| l.side == Side::Buy).map(|l| l.price * l.quantity as f64).sum();
+                let sold: f64 = anomaly.legs.iter().filter(|l| l.side == Side::Sell).map(|l| l.price * l.quantity as f64).sum();
+                let costs = calculate_options_costs(bought, sold, anomaly.legs.len());
+
+                let mut opportunity = new_opportunity(StrategyKind::VolatilitySurface, &self.symbol, anomaly.legs.clone(), anomaly.edge, costs, provenance("volatility_surface", source, &["option_chain"]));
+                opportunity.expiry = Some(anomaly.expiry_date.clone());
+                opportunity.details = format!("{:?}: {}", anomaly.kind, anomaly.details);
+                opportunity
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opportunity::YAHOO_DELAYED;
+    use crate::options_arbitrage::black_scholes_price;
+
+    fn smile_iv(k: f64) -> f64 {
+        0.22 - 0.15 * k + 0.6 * k * k
+    }
+
+    fn build_chain(spot: f64, expiry: &str, t: f64, r: f64, iv_bump: Option<(f64, f64)>) -> Vec<OptionContract> {
+        let forward = spot * (r * t).exp();
+        (0..11)
+            .map(|i| {
+                let strike = spot * (0.85 + 0.03 * i as f64);
+                let mut iv = smile_iv((strike / forward).ln());
+                if let Some((bump_strike, bump)) = iv_bump && (strike - bump_strike).abs() < 1e-9 {
+                    iv += bump;
+                }
+                OptionContract {
+                    symbol: "TEST".to_string(),
+                    strike_price: strike,
+                    expiry_date: expiry.to_string(),
+                    call_price: black_scholes_price(OptionType::Call, spot, strike, r, t, iv),
+                    put_price: black_scholes_price(OptionType::Put, spot, strike, r, t, iv),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_svi_fit_recovers_smooth_smile() {
+        let as_of = NaiveDate::from_ymd_opt(2025, 11, 1).unwrap();
+        let t = time_to_expiry_years("26-Dec-2025", as_of).unwrap();
+        let chain = build_chain(1000.0, "26-Dec-2025", t, 0.065, None);
+
+        let scan = scan_volatility_surface("TEST", 1000.0, &chain, as_of, &SurfaceConfig::default());
+        assert_eq!(scan.smiles.len(), 1);
+        assert!(scan.smiles[0].fit_rmse.unwrap() < 0.005);
+        assert!(!scan.has_anomalies);
+    }
+
+    #[test]
+    fn test_flags_iv_outlier() {
+        let as_of = NaiveDate::from_ymd_opt(2025, 11, 1).unwrap();
+        let t = time_to_expiry_years("26-Dec-2025", as_of).unwrap();
+        let chain = build_chain(1000.0, "26-Dec-2025", t, 0.065, Some((1000.0 * 1.06, 0.08)));
+
+        let scan = scan_volatility_surface("TEST", 1000.0, &chain, as_of, &SurfaceConfig::default());
+        assert!(scan.anomalies.iter().any(|a| a.kind == AnomalyKind::IvDeviation && (a.strike - 1060.0).abs() < 1e-6));
+
+        let opportunities = scan.to_opportunities(YAHOO_DELAYED);
+        assert!(!opportunities.is_empty());
+        assert_eq!(opportunities[0].legs[0].side, Side::Sell);
+        let premium = |side: Side| opportunities[0].legs.iter().filter(|l| l.side == side).map(|l| l.price * l.quantity as f64).sum::<f64>();
+        let expected = calculate_options_costs(premium(Side::Buy), premium(Side::Sell), opportunities[0].legs.len());
+        assert!((opportunities[0].costs - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_flags_butterfly_and_calendar_violations() {
+        let as_of = NaiveDate::from_ymd_opt(2025, 11, 1).unwrap();
+        let t_near = time_to_expiry_years("26-Dec-2025", as_of).unwrap();
+        let mut chain = build_chain(1000.0, "26-Dec-2025", t_near, 0.065, None);
+        chain[5].call_price += 20.0;
+
+        // the later expiry quoted at a fraction of the near vol has less total variance
+        let t_far = time_to_expiry_years("30-Jan-2026", as_of).unwrap();
+        let far: Vec<OptionContract> = build_chain(1000.0, "30-Jan-2026", t_far, 0.065, None)
+            .into_iter()
+            .map(|mut c| {
+                let r = 0.065;
+                c.call_price = black_scholes_price(OptionType::Call, 1000.0, c.strike_price, r, t_far, 0.12);
+                c.put_price = black_scholes_price(OptionType::Put, 1000.0, c.strike_price, r, t_far, 0.12);
+                c
+            })
+            .collect();
+        chain.extend(far);
+
+        let scan = scan_volatility_surface("TEST", 1000.0, &chain, as_of, &SurfaceConfig::default());
+        assert!(scan.anomalies.iter().any(|a| a.kind == AnomalyKind::ButterflyArbitrage));
+        assert!(scan.anomalies.iter().any(|a| a.kind == AnomalyKind::CalendarArbitrage));
+    }
+}
@@ -0,0 +1,393 @@
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use std::collections::HashMap;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use crate::data_logger::LoggedObservation;
+use crate::strategy_backtester::{run_strategy_backtest, StrategyBacktestParams, StrategyBacktestResponse};
+use crate::validation::{FieldError, FieldErrors, Validate};
+
+// every fold backtests every candidate twice, so bound both the grid and the number of folds
+pub const MAX_GRID_SIZE: usize = 1000;
+pub const MIN_WINDOW_SECS: i64 = 600;
+pub const MAX_WINDOW_SECS: i64 = 90 * 86400;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SearchMethod {
+    Grid,
+    // evaluates a seeded random subset of the grid
+    Random { samples: usize, seed: u64 },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    NetPnl,
+    Sharpe,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub struct WalkForwardParams {
+    pub symbols: Vec<String>, // empty means every symbol in the history
+    pub entry_thresholds: Vec<f64>,
+    pub exit_thresholds: Vec<f64>,
+    pub max_holding_secs: Vec<Option<i64>>,
+    pub search: SearchMethod,
+    pub objective: Objective,
+    // rolling windows: fit on train_secs, evaluate on the following test_secs, then step by test_secs
+    pub train_secs: i64,
+    pub test_secs: i64,
+    pub slippage_bps: f64,
+    pub lots: u32,
+}
+
+impl Default for WalkForwardParams {
+    fn default() -> Self {
+        WalkForwardParams {
+            symbols: Vec::new(),
+            entry_thresholds: vec![0.3, 0.4, 0.5, 0.6, 0.8, 1.0],
+            exit_thresholds: vec![0.0, 0.1, 0.2],
+            max_holding_secs: vec![None, Some(1800), Some(3600)],
+            search: SearchMethod::Grid,
+            objective: Objective::NetPnl,
+            train_secs: 6 * 3600,
+            test_secs: 2 * 3600,
+            slippage_bps: 2.0,
+            lots: 1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+pub struct Candidate {
+    pub entry_threshold: f64,
+    pub exit_threshold: f64,
+    pub max_holding_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct WalkForwardFold {
+    pub train_start: String,
+    pub test_start: String,
+    pub test_end: String,
+    pub chosen: Candidate,
+    pub in_sample_score: f64,
+    pub out_of_sample_score: f64,
+    pub out_of_sample_trades: usize,
+    pub out_of_sample_net_pnl: f64,
+    // 0.0 is the best of all candidates out-of-sample, 1.0 the worst
+    pub out_of_sample_rank: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct OverfittingDiagnostics {
+    // out-of-sample score per second over in-sample score per second; near 1 is robust
+    pub walk_forward_efficiency: f64,
+    // share of folds whose in-sample winner landed in the bottom half out-of-sample
+    pub probability_of_overfitting: f64,
+    pub average_in_sample_score: f64,
+    pub average_out_of_sample_score: f64,
+    // distinct in-sample winners over number of folds; low means the choice is stable
+    pub parameter_instability: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct SymbolWalkForward {
+    pub symbol: String,
+    pub folds: Vec<WalkForwardFold>,
+    // best median out-of-sample score across folds, not the best single fit
+    pub recommended: Option<Candidate>,
+    pub most_selected: Option<Candidate>,
+    pub total_out_of_sample_net_pnl: f64,
+    pub diagnostics: OverfittingDiagnostics,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct WalkForwardResponse {
+    pub candidates_evaluated: usize,
+    pub symbols: Vec<SymbolWalkForward>,
+}
+
+impl Validate for WalkForwardParams {
+    type Output = WalkForwardParams;
+
+    fn validate(self) -> Result<WalkForwardParams, Vec<FieldError>> {
+        let mut errors = FieldErrors::default();
+        let window = format!("must be between {} and {} seconds", MIN_WINDOW_SECS, MAX_WINDOW_SECS);
+        errors.check((MIN_WINDOW_SECS..=MAX_WINDOW_SECS).contains(&self.train_secs), "train_secs", window.clone());
+        errors.check((MIN_WINDOW_SECS..=MAX_WINDOW_SECS).contains(&self.test_secs), "test_secs", window);
+        for (field, thresholds) in [("entry_thresholds", &self.entry_thresholds), ("exit_thresholds", &self.exit_thresholds)] {
+            errors.check(!thresholds.is_empty(), field, "must not be empty");
+            errors.check(thresholds.iter().all(|t| t.is_finite() && *t >= 0.0), field, "must be finite and not negative");
+        }
+        errors.check(!self.max_holding_secs.is_empty(), "max_holding_secs", "must not be empty");
+        errors.check(self.max_holding_secs.iter().flatten().all(|s| *s > 0), "max_holding_secs", "must be positive or null");
+        let grid_size = self.entry_thresholds.len().saturating_mul(self.exit_thresholds.len()).saturating_mul(self.max_holding_secs.len());
+        errors.check(grid_size <= MAX_GRID_SIZE, "entry_thresholds", format!("times exit_thresholds times max_holding_secs is limited to {}", MAX_GRID_SIZE));
+        if let SearchMethod::Random { samples, .. } = self.search {
+            errors.check(samples > 0 && samples <= MAX_GRID_SIZE, "search.samples", format!("must be between 1 and {}", MAX_GRID_SIZE));
+        }
+        errors.check(self.slippage_bps.is_finite() && self.slippage_bps >= 0.0, "slippage_bps", "must be finite and not negative");
+        errors.check(self.lots >= 1, "lots", "must be at least 1");
+        errors.into_result()?;
+        Ok(self)
+    }
+}
+
+pub fn candidate_grid(params: &WalkForwardParams) -> Vec<Candidate> {
+    let mut grid = Vec::new();
+    for &entry_threshold in &params.entry_thresholds {
+        for &exit_threshold in &params.exit_thresholds {
+            // exiting above the entry level would close every trade immediately
+            if exit_threshold >= entry_threshold {
+                continue;
+            }
+            for &max_holding_secs in &params.max_holding_secs {
+                grid.push(Candidate { entry_threshold, exit_threshold, max_holding_secs });
+            }
+        }
+    }
+
+    if let SearchMethod::Random { samples, seed } = params.search {
+        grid.shuffle(&mut StdRng::seed_from_u64(seed));
+        grid.truncate(samples.max(1));
+    }
+    grid
+}
+
+fn backtest(window: &[LoggedObservation], candidate: &Candidate, params: &WalkForwardParams) -> StrategyBacktestResponse {
+    let strategy = StrategyBacktestParams {
+        entry_threshold: candidate.entry_threshold,
+        exit_threshold: candidate.exit_threshold,
+        max_holding_secs: candidate.max_holding_secs,
+        slippage_bps: params.slippage_bps,
+        lots: params.lots,
+        ..StrategyBacktestParams::default()
+    };
+    run_strategy_backtest(window, &strategy)
+}
+
+fn score(result: &StrategyBacktestResponse, objective: Objective) -> f64 {
+    match objective {
+        Objective::NetPnl => result.total_net_pnl,
+        Objective::Sharpe => result.sharpe_ratio,
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2.0 } else { values[mid] }
+}
+
+fn walk_forward_symbol(symbol: &str, history: &[LoggedObservation], candidates: &[Candidate], params: &WalkForwardParams) -> SymbolWalkForward {
+    let mut folds = Vec::new();
+    // out-of-sample scores of every candidate, per fold, for the robust choice
+    let mut candidate_scores: Vec<Vec<f64>> = vec![Vec::new(); candidates.len()];
+    let mut selections: HashMap<usize, usize> = HashMap::new();
+    let (mut in_sample_total, mut out_of_sample_total) = (0.0, 0.0);
+    let mut overfit_folds = 0;
+
+    if let (Some(first), Some(last)) = (history.first(), history.last()) {
+        let mut train_start = first.timestamp;
+        loop {
+            let test_start = train_start + chrono::Duration::seconds(params.train_secs);
+            let test_end = test_start + chrono::Duration::seconds(params.test_secs);
+            if test_start > last.timestamp {
+                break;
+            }
+
+            let from = history.partition_point(|o| o.timestamp < train_start);
+            let split = history.partition_point(|o| o.timestamp < test_start);
+            let to = history.partition_point(|o| o.timestamp < test_end);
+            let (train, test) = (&history[from..split], &history[split..to]);
+
+            if !train.is_empty() && !test.is_empty() {
+                let evaluated: Vec<(f64, StrategyBacktestResponse)> = candidates
+                    .par_iter()
+                    .map(|candidate| {
+                        let in_sample = score(&backtest(train, candidate, params), params.objective);
+                        (in_sample, backtest(test, candidate, params))
+                    })
+                    .collect();
+
+                // ties go to the earlier, i.e. more conservative, grid entry
+                let best = (0..candidates.len())
+                    .fold(0, |best, i| if evaluated[i].0 > evaluated[best].0 { i } else { best });
+                let out_of_sample: Vec<f64> = evaluated.iter().map(|(_, r)| score(r, params.objective)).collect();
+                for (scores, s) in candidate_scores.iter_mut().zip(&out_of_sample) {
+                    scores.push(*s);
+                }
+
+                let beaten_by = out_of_sample.iter().filter(|s| **s > out_of_sample[best]).count();
+                let rank = if candidates.len() > 1 { beaten_by as f64 / (candidates.len() - 1) as f64 } else { 0.0 };
+                if rank > 0.5 {
+                    overfit_folds += 1;
+                }
+                *selections.entry(best).or_default() += 1;
+                in_sample_total += evaluated[best].0;
+                out_of_sample_total += out_of_sample[best];
+
+                folds.push(WalkForwardFold {
+                    train_start: train_start.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    test_start: test_start.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    test_end: test_end.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    chosen: candidates[best],
+                    in_sample_score: evaluated[best].0,
+                    out_of_sample_score: out_of_sample[best],
+                    out_of_sample_trades: evaluated[best].1.total_trades,
+                    out_of_sample_net_pnl: evaluated[best].1.total_net_pnl,
+                    out_of_sample_rank: rank,
+                });
+            }
+
+            train_start += chrono::Duration::seconds(params.test_secs.max(1));
+        }
+    }
+
+    let fold_count = folds.len() as f64;
+    let recommended = candidate_scores
+        .iter_mut()
+        .enumerate()
+        .filter(|(_, scores)| !scores.is_empty())
+        .map(|(i, scores)| (i, median(scores)))
+        .fold(None, |best: Option<(usize, f64)>, (i, m)| match best {
+            Some((_, best_median)) if best_median >= m => best,
+            _ => Some((i, m)),
+        })
+        .map(|(i, _)| candidates[i]);
+    let most_selected = selections
+        .iter()
+        .max_by_key(|(i, count)| (**count, std::cmp::Reverse(**i)))
+        .map(|(i, _)| candidates[*i]);
+
+    let in_sample_rate = in_sample_total / params.train_secs.max(1) as f64;
+    let out_of_sample_rate = out_of_sample_total / params.test_secs.max(1) as f64;
+
+    SymbolWalkForward {
+        symbol: symbol.to_string(),
+        recommended,
+        most_selected,
+        total_out_of_sample_net_pnl: folds.iter().map(|f| f.out_of_sample_net_pnl).sum(),
+        diagnostics: OverfittingDiagnostics {
+            walk_forward_efficiency: if in_sample_rate.abs() > f64::EPSILON { out_of_sample_rate / in_sample_rate } else { 0.0 },
+            probability_of_overfitting: if folds.is_empty() { 0.0 } else { overfit_folds as f64 / fold_count },
+            average_in_sample_score: if folds.is_empty() { 0.0 } else { in_sample_total / fold_count },
+            average_out_of_sample_score: if folds.is_empty() { 0.0 } else { out_of_sample_total / fold_count },
+            parameter_instability: if folds.is_empty() { 0.0 } else { selections.len() as f64 / fold_count },
+        },
+        folds,
+    }
+}
+
+pub fn run_walk_forward(observations: &[LoggedObservation], params: &WalkForwardParams) -> WalkForwardResponse {
+    let candidates = candidate_grid(params);
+
+    let mut by_symbol: HashMap<&str, Vec<LoggedObservation>> = HashMap::new();
+    for observation in observations {
+        if params.symbols.is_empty() || params.symbols.contains(&observation.symbol) {
+            by_symbol.entry(observation.symbol.as_str()).or_default().push(observation.clone());
+        }
+    }
+
+    let mut symbols: Vec<SymbolWalkForward> = by_symbol
+        .iter_mut()
+        .filter(|_| !candidates.is_empty())
+        .map(|(symbol, history)| {
+            history.sort_by_key(|o| o.timestamp);
+            walk_forward_symbol(symbol, history, &candidates, params)
+        })
+        .collect();
+    symbols.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+    WalkForwardResponse {
+        candidates_evaluated: candidates.len(),
+        symbols,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    // the basis opens to 1% every 20 minutes and closes within 5
+    fn cycling_history(hours: i64) -> Vec<LoggedObservation> {
+        let start = NaiveDateTime::parse_from_str("2026-06-25 09:15:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        (0..hours * 60)
+            .map(|minute| {
+                let spread = if minute % 20 < 5 { 1.0 } else { 0.05 };
+                LoggedObservation {
+                    timestamp: start + chrono::Duration::minutes(minute),
+                    symbol: "RELIANCE".to_string(),
+                    spot_price: 1000.0,
+                    futures_price: 1000.0 * (1.0 + spread / 100.0),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_validation_bounds_windows_and_grid() {
+        assert!(WalkForwardParams::default().validate().is_ok());
+
+        let invalid = WalkForwardParams {
+            train_secs: i64::MAX,
+            test_secs: 1,
+            entry_thresholds: (0..100).map(|i| i as f64 / 10.0).collect(),
+            exit_thresholds: (0..20).map(|i| i as f64 / 100.0).collect(),
+            slippage_bps: f64::NAN,
+            lots: 0,
+            ..WalkForwardParams::default()
+        };
+        let errors = invalid.validate().unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["train_secs", "test_secs", "entry_thresholds", "slippage_bps", "lots"]);
+
+        let random = WalkForwardParams { search: SearchMethod::Random { samples: 0, seed: 1 }, ..WalkForwardParams::default() };
+        assert_eq!(random.validate().unwrap_err()[0].field, "search.samples");
+    }
+
+    #[test]
+    fn test_candidate_grid_skips_inverted_rules_and_samples() {
+        let params = WalkForwardParams {
+            entry_thresholds: vec![0.2, 0.5],
+            exit_thresholds: vec![0.1, 0.3],
+            max_holding_secs: vec![None],
+            ..WalkForwardParams::default()
+        };
+        assert_eq!(candidate_grid(&params).len(), 3);
+
+        let random = WalkForwardParams { search: SearchMethod::Random { samples: 5, seed: 3 }, ..WalkForwardParams::default() };
+        let sample = candidate_grid(&random);
+        assert_eq!(sample.len(), 5);
+        assert_eq!(sample, candidate_grid(&random));
+    }
+
+    #[test]
+    fn test_walk_forward_finds_profitable_threshold() {
+        let params = WalkForwardParams {
+            entry_thresholds: vec![0.5, 2.0],
+            exit_thresholds: vec![0.1],
+            max_holding_secs: vec![None],
+            train_secs: 2 * 3600,
+            test_secs: 3600,
+            slippage_bps: 0.0,
+            ..WalkForwardParams::default()
+        };
+        let result = run_walk_forward(&cycling_history(6), &params);
+
+        let symbol = &result.symbols[0];
+        assert_eq!(symbol.folds.len(), 4);
+        assert!(symbol.folds.iter().all(|f| f.chosen.entry_threshold == 0.5));
+        assert_eq!(symbol.recommended.unwrap().entry_threshold, 0.5);
+        assert!(symbol.total_out_of_sample_net_pnl > 0.0);
+        assert_eq!(symbol.diagnostics.probability_of_overfitting, 0.0);
+        assert_eq!(symbol.diagnostics.parameter_instability, 0.25);
+        assert!(symbol.diagnostics.walk_forward_efficiency > 0.5);
+    }
+}
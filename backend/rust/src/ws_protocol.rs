@@ -0,0 +1,359 @@
+use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use crate::arbitrage_detector::ArbitrageResult;
+use crate::engine_state::normalize_symbol;
+use crate::feed::{Ack, DetectorResult, Envelope, FeedMessage, Heartbeat, Snapshot};
+use crate::opportunity::StrategyKind;
+use crate::stat_arbitrage::SignalKind;
+
+// The server pings this often; a client silent for IDLE_TIMEOUT_SECS (pongs count)
+// is disconnected
+pub const HEARTBEAT_INTERVAL_SECS: u64 = 15;
+pub const IDLE_TIMEOUT_SECS: u64 = 45;
+// bounds for set_filters max_messages_per_sec; the throttle interval is 1/rate
+pub const MIN_MESSAGES_PER_SEC: f64 = 0.01;
+pub const MAX_MESSAGES_PER_SEC: f64 = 1000.0;
+// per-connection cap on subscribed symbols
+pub const MAX_SUBSCRIBED_SYMBOLS: usize = 100;
+
+// Commands a /ws client may send; every command is answered with an ack or an error frame
+#[derive(Debug, Deserialize, Clone, PartialEq, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ClientCommand {
+    Subscribe {
+        #[serde(default)]
+        symbols: Vec<String>,
+        #[serde(default)]
+        strategies: Vec<StrategyKind>,
+        request_id: Option<String>,
+    },
+    Unsubscribe {
+        #[serde(default)]
+        symbols: Vec<String>,
+        #[serde(default)]
+        strategies: Vec<StrategyKind>,
+        request_id: Option<String>,
+    },
+    SetFilters {
+        opportunities_only: Option<bool>,
+        min_spread_pct: Option<f64>,
+        max_messages_per_sec: Option<f64>,
+        request_id: Option<String>,
+    },
+    // for browsers, which cannot send protocol-level pings; answered with a heartbeat
+    Ping {
+        request_id: Option<String>,
+    },
+}
+
+impl ClientCommand {
+    fn name(&self) -> &'static str {
+        match self {
+            ClientCommand::Subscribe { .. } => "subscribe",
+            ClientCommand::Unsubscribe { .. } => "unsubscribe",
+            ClientCommand::SetFilters { .. } => "set_filters",
+            ClientCommand::Ping { .. } => "ping",
+        }
+    }
+
+    fn request_id(&self) -> Option<String> {
+        match self {
+            ClientCommand::Subscribe { request_id, .. }
+            | ClientCommand::Unsubscribe { request_id, .. }
+            | ClientCommand::SetFilters { request_id, .. }
+            | ClientCommand::Ping { request_id } => request_id.clone(),
+        }
+    }
+}
+
+// Empty symbol and strategy sets mean everything, so a client that never
+// subscribes keeps receiving the full feed
+#[derive(Debug, Serialize, Clone, Default, JsonSchema)]
+pub struct Subscription {
+    pub symbols: HashSet<String>,
+    pub strategies: HashSet<StrategyKind>,
+    pub opportunities_only: bool,
+    pub min_spread_pct: Option<f64>,
+    pub max_messages_per_sec: Option<f64>,
+}
+
+// What the filters need to know about a feed message
+#[derive(Debug, PartialEq)]
+pub struct FeedItem {
+    pub symbol: String,
+    pub strategy: StrategyKind,
+    pub is_opportunity: bool,
+    pub spread_pct: f64,
+}
+
+// Quotes are cash-futures results; status, alerts and per-connection replies are
+// not subject to the filters. Detections without a spread (surface scans, stat-arb
+// signals) report 0, and every stat-arb signal but Hold counts as actionable
+pub fn classify_feed_message(message: &FeedMessage) -> Option<FeedItem> {
+    match message {
+        FeedMessage::Quote(result) => Some(quote_item(result)),
+        FeedMessage::Opportunity(opportunity) => Some(FeedItem {
+            symbol: opportunity.symbol.clone(),
+            strategy: opportunity.kind,
+            is_opportunity: true,
+            spread_pct: opportunity.edge_percentage.abs(),
+        }),
+        FeedMessage::Detection(detection) => Some(detection_item(&detection.result)),
+        _ => None,
+    }
+}
+
+fn detection_item(result: &DetectorResult) -> FeedItem {
+    let (symbol, strategy, is_opportunity, spread_pct) = match result {
+        DetectorResult::VolatilitySurface(scan) => (&scan.symbol, StrategyKind::VolatilitySurface, scan.has_anomalies, 0.0),
+        DetectorResult::IndexBasket(result) => (&result.index, StrategyKind::IndexBasket, result.opportunity, result.mispricing_percentage.abs()),
+        DetectorResult::CrossExchange(result) => (&result.symbol, StrategyKind::CrossExchange, result.opportunity, result.spread_percentage.abs()),
+        DetectorResult::StatisticalArbitrage(signal) => (&signal.pair, StrategyKind::StatisticalArbitrage, signal.signal != SignalKind::Hold, 0.0),
+    };
+    FeedItem { symbol: symbol.clone(), strategy, is_opportunity, spread_pct }
+}
+
+fn quote_item(result: &ArbitrageResult) -> FeedItem {
+    FeedItem {
+        symbol: result.symbol.clone(),
+        strategy: StrategyKind::CashFutures,
+        is_opportunity: result.opportunity,
+        spread_pct: result.spread_percentage.abs(),
+    }
+}
+
+impl Subscription {
+    pub fn matches(&self, item: &FeedItem) -> bool {
+        (self.symbols.is_empty() || self.symbols.contains(&item.symbol))
+            && (self.strategies.is_empty() || self.strategies.contains(&item.strategy))
+            && (!self.opportunities_only || item.is_opportunity)
+            && self.min_spread_pct.is_none_or(|min| item.spread_pct >= min)
+    }
+
+    // applies the command and returns the ack frame, or the error frame if it was rejected
+    pub fn apply(&mut self, command: ClientCommand) -> FeedMessage {
+        let (action, request_id) = (command.name(), command.request_id());
+        match command {
+            ClientCommand::Subscribe { symbols, strategies, .. } => {
+                let symbols = match normalize_symbols(&symbols) {
+                    Ok(symbols) => symbols,
+                    Err(e) => return FeedMessage::error(&e, request_id),
+                };
+                if self.symbols.union(&symbols).count() > MAX_SUBSCRIBED_SYMBOLS {
+                    let message = format!("subscriptions are limited to {} symbols", MAX_SUBSCRIBED_SYMBOLS);
+                    return FeedMessage::error(&message, request_id);
+                }
+                self.symbols.extend(symbols);
+                self.strategies.extend(strategies);
+            }
+            ClientCommand::Unsubscribe { symbols, strategies, .. } => {
+                let symbols = match normalize_symbols(&symbols) {
+                    Ok(symbols) => symbols,
+                    Err(e) => return FeedMessage::error(&e, request_id),
+                };
+                for symbol in &symbols {
+                    self.symbols.remove(symbol);
+                }
+                for strategy in &strategies {
+                    self.strategies.remove(strategy);
+                }
+            }
+            ClientCommand::SetFilters { opportunities_only, min_spread_pct, max_messages_per_sec, .. } => {
+                if let Some(rate) = max_messages_per_sec && !(MIN_MESSAGES_PER_SEC..=MAX_MESSAGES_PER_SEC).contains(&rate) {
+                    let message = format!("max_messages_per_sec must be between {} and {}", MIN_MESSAGES_PER_SEC, MAX_MESSAGES_PER_SEC);
+                    return FeedMessage::error(&message, request_id);
+                }
+                if let Some(min) = min_spread_pct && min < 0.0 {
+                    return FeedMessage::error("min_spread_pct must not be negative", request_id);
+                }
+                if let Some(only) = opportunities_only {
+                    self.opportunities_only = only;
+                }
+                if min_spread_pct.is_some() {
+                    self.min_spread_pct = min_spread_pct;
+                }
+                if max_messages_per_sec.is_some() {
+                    self.max_messages_per_sec = max_messages_per_sec;
+                }
+            }
+            ClientCommand::Ping { .. } => {
+                return FeedMessage::Heartbeat(Heartbeat { interval_secs: HEARTBEAT_INTERVAL_SECS, request_id });
+            }
+        }
+        FeedMessage::Ack(Box::new(Ack {
+            action: action.to_string(),
+            request_id,
+            subscription: self.clone(),
+        }))
+    }
+}
+
+// Symbols are matched as the engine publishes them, so they go through the watchlist's
+// normalize_symbol; stat-arb pairs ("HDFCBANK/ICICIBANK") are checked half by half
+fn normalize_symbols(symbols: &[String]) -> Result<HashSet<String>, String> {
+    symbols
+        .iter()
+        .map(|symbol| {
+            let halves = symbol.splitn(2, '/').map(normalize_symbol).collect::<Result<Vec<_>, _>>()?;
+            Ok(halves.join("/"))
+        })
+        .collect()
+}
+
+pub fn parse_command(text: &str) -> Result<ClientCommand, FeedMessage> {
+    serde_json::from_str(text).map_err(|e| FeedMessage::error(&format!("invalid command: {}", e), None))
+}
+
+// Numbers every frame sent on one connection, so a client can spot gaps and order
+// frames without relying on timestamps
+#[derive(Default)]
+pub struct Sequencer {
+    next: u64,
+}
+
+impl Sequencer {
+    pub fn stamp(&mut self, message: &FeedMessage) -> String {
+        self.next += 1;
+        serde_json::to_string(&Envelope::new(self.next, message)).unwrap()
+    }
+}
+
+// Latest state for every symbol the subscription covers; sent on connect, and again
+// with reason "resync" when the client fell behind the broadcast channel
+pub fn snapshot_frame(reason: &str, missed: u64, results: Vec<ArbitrageResult>, subscription: &Subscription) -> FeedMessage {
+    let results = results
+        .into_iter()
+        .filter(|r| subscription.matches(&quote_item(r)))
+        .collect();
+    FeedMessage::Snapshot(Snapshot { reason: reason.to_string(), missed, results })
+}
+
+// Drops messages that arrive sooner than 1/rate after the last one let through
+#[derive(Default)]
+pub struct Throttle {
+    last_sent: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn allow(&mut self, max_messages_per_sec: Option<f64>, now: Instant) -> bool {
+        let Some(rate) = max_messages_per_sec else {
+            return true;
+        };
+        let interval = Duration::from_secs_f64(1.0 / rate);
+        if self.last_sent.is_some_and(|last| now.duration_since(last) < interval) {
+            return false;
+        }
+        self.last_sent = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage_detector::detect_cash_futures_arbitrage;
+    use crate::opportunity::{ToOpportunities, YAHOO_DELAYED};
+
+    // a 0.6% threshold makes spreads above it opportunities
+    fn quote(symbol: &str, spread_percentage: f64) -> ArbitrageResult {
+        detect_cash_futures_arbitrage(symbol, 1000.0, 1000.0 * (1.0 + spread_percentage / 100.0), 0.6)
+    }
+
+    fn item(symbol: &str, spread_percentage: f64) -> FeedItem {
+        classify_feed_message(&FeedMessage::Quote(Box::new(quote(symbol, spread_percentage)))).unwrap()
+    }
+
+    #[test]
+    fn test_commands_update_subscription() {
+        let mut subscription = Subscription::default();
+        let command = parse_command(r#"{"action":"subscribe","symbols":["TCS","INFY"],"strategies":["CASH_FUTURES"],"request_id":"1"}"#).unwrap();
+        let FeedMessage::Ack(ack) = subscription.apply(command) else {
+            panic!("expected an ack");
+        };
+        assert_eq!(ack.request_id.as_deref(), Some("1"));
+
+        assert!(subscription.matches(&item("TCS", -0.7)));
+        assert!(!subscription.matches(&item("SBIN", 0.7)));
+
+        subscription.apply(parse_command(r#"{"action":"unsubscribe","symbols":["TCS"]}"#).unwrap());
+        assert!(!subscription.matches(&item("TCS", -0.7)));
+
+        subscription.apply(parse_command(r#"{"action":"subscribe","symbols":[" sbin ","hdfcbank/icicibank"]}"#).unwrap());
+        assert!(subscription.matches(&item("SBIN", 0.7)));
+        assert!(subscription.symbols.contains("HDFCBANK/ICICIBANK"));
+        subscription.apply(parse_command(r#"{"action":"unsubscribe","symbols":["sbin","HDFCBANK/ICICIBANK"]}"#).unwrap());
+        assert!(!subscription.matches(&item("SBIN", 0.7)));
+
+        subscription.apply(parse_command(r#"{"action":"set_filters","opportunities_only":true,"min_spread_pct":0.5}"#).unwrap());
+        assert!(!subscription.matches(&item("INFY", 0.4)));
+        assert!(!subscription.matches(&item("INFY", 0.55)));
+        assert!(subscription.matches(&item("INFY", -0.7)));
+    }
+
+    #[test]
+    fn test_bad_commands_produce_error_frames() {
+        assert!(matches!(parse_command(r#"{"action":"dance"}"#), Err(FeedMessage::Error(_))));
+
+        let mut subscription = Subscription::default();
+        let rejected = subscription.apply(parse_command(r#"{"action":"set_filters","max_messages_per_sec":0,"request_id":"7"}"#).unwrap());
+        let FeedMessage::Error(error) = rejected else {
+            panic!("expected an error frame");
+        };
+        assert_eq!(error.request_id.as_deref(), Some("7"));
+        assert!(subscription.max_messages_per_sec.is_none());
+        for rate in ["1e-300", "-1", "1e6"] {
+            let rejected = subscription.apply(parse_command(&format!(r#"{{"action":"set_filters","max_messages_per_sec":{}}}"#, rate)).unwrap());
+            assert!(matches!(rejected, FeedMessage::Error(_)), "rate {} accepted", rate);
+        }
+        assert!(subscription.max_messages_per_sec.is_none());
+
+        let rejected = subscription.apply(parse_command(r#"{"action":"subscribe","symbols":["TCS","TCS.NS"],"request_id":"9"}"#).unwrap());
+        assert!(matches!(rejected, FeedMessage::Error(ref error) if error.request_id.as_deref() == Some("9")));
+        assert!(subscription.symbols.is_empty());
+        let too_many: Vec<String> = (0..=MAX_SUBSCRIBED_SYMBOLS).map(|i| format!("SYM{}", i)).collect();
+        let command = serde_json::json!({ "action": "subscribe", "symbols": too_many }).to_string();
+        assert!(matches!(subscription.apply(parse_command(&command).unwrap()), FeedMessage::Error(_)));
+        assert!(subscription.symbols.is_empty());
+
+        let pong = subscription.apply(parse_command(r#"{"action":"ping","request_id":"8"}"#).unwrap());
+        assert!(matches!(pong, FeedMessage::Heartbeat(Heartbeat { interval_secs: HEARTBEAT_INTERVAL_SECS, .. })));
+    }
+
+    #[test]
+    fn test_opportunity_classification_and_throttle() {
+        let opportunity = quote("TCS", 0.8).to_opportunities(YAHOO_DELAYED).remove(0);
+        let item = classify_feed_message(&FeedMessage::Opportunity(Box::new(opportunity))).unwrap();
+        assert_eq!(item.strategy, StrategyKind::CashFutures);
+        assert!(item.is_opportunity);
+        assert!(classify_feed_message(&FeedMessage::error("x", None)).is_none());
+
+        let mut throttle = Throttle::default();
+        let start = Instant::now();
+        assert!(throttle.allow(Some(2.0), start));
+        assert!(!throttle.allow(Some(2.0), start + Duration::from_millis(200)));
+        assert!(throttle.allow(Some(2.0), start + Duration::from_millis(600)));
+        assert!(throttle.allow(None, start + Duration::from_millis(601)));
+    }
+
+    #[test]
+    fn test_snapshot_respects_subscription_and_frames_are_sequenced() {
+        let mut subscription = Subscription::default();
+        subscription.apply(parse_command(r#"{"action":"subscribe","symbols":["TCS"]}"#).unwrap());
+        let snapshot = snapshot_frame("resync", 12, vec![quote("TCS", 0.2), quote("INFY", 0.3)], &subscription);
+        let FeedMessage::Snapshot(ref payload) = snapshot else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(payload.results.len(), 1);
+        assert_eq!(payload.missed, 12);
+
+        let mut sequencer = Sequencer::default();
+        let first: serde_json::Value = serde_json::from_str(&sequencer.stamp(&snapshot)).unwrap();
+        let second: serde_json::Value = serde_json::from_str(&sequencer.stamp(&FeedMessage::Quote(Box::new(quote("TCS", 0.1))))).unwrap();
+        assert_eq!(first["seq"], 1);
+        assert_eq!(first["type"], "snapshot");
+        assert_eq!(second["seq"], 2);
+        assert_eq!(second["payload"]["symbol"], "TCS");
+    }
+}
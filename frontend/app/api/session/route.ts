@@ -0,0 +1,26 @@
+import { NextResponse } from 'next/server';
+
+// The engine's API key stays on this server; browsers get a short-lived read-only
+// token from the engine instead. Leave ENGINE_API_KEY unset when the engine runs
+// without AUTH_CONFIG.
+const ENGINE_URL = process.env.ENGINE_URL || 'http://127.0.0.1:3030';
+
+export const dynamic = 'force-dynamic';
+
+export async function POST() {
+  const apiKey = process.env.ENGINE_API_KEY;
+  if (!apiKey) {
+    return NextResponse.json({ token: null, scope: 'read_only', expires_at: null });
+  }
+  try {
+    const res = await fetch(`${ENGINE_URL}/api/session`, {
+      method: 'POST',
+      headers: { 'x-api-key': apiKey },
+      cache: 'no-store',
+    });
+    return NextResponse.json(await res.json(), { status: res.status, headers: { 'Cache-Control': 'no-store' } });
+  } catch (e) {
+    console.error('Session request to the engine failed:', e);
+    return NextResponse.json({ error: 'engine unreachable' }, { status: 502 });
+  }
+}
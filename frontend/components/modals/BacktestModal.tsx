@@ -5,6 +5,7 @@ import { ChartDataPoint, BacktestResult, Trade, MonteCarloResponse } from '@/lib
 import toast from 'react-hot-toast';
 import { EquityCurveChart } from '@/components/charts/EquityCurveChart';
 import { DrawdownChart } from '@/components/charts/DrawdownChart';
+import { getSessionToken } from '@/lib/session';
 
 interface BacktestModalProps {
   selectedStock: string;
@@ -143,10 +144,12 @@ export function BacktestModal({
             avg_loss_pct: -(avgLossAmount / capital), // keep it negative
         };
 
+        const token = await getSessionToken();
         const res = await fetch("http://127.0.0.1:3030/api/backtest", {
             method: "POST",
             headers: {
-                "Content-Type": "application/json"
+                "Content-Type": "application/json",
+                ...(token ? { "Authorization": `Bearer ${token}` } : {})
             },
             body: JSON.stringify(params)
         });
@@ -1,9 +1,10 @@
 "use client";
 
 import { useEffect, useState } from 'react';
-import { ArbitrageData, ChartDataPoint } from '@/lib/types';
+import { ArbitrageData, ChartDataPoint, FeedEnvelope, FeedSnapshot } from '@/lib/types';
 import { useLocalStorage, loadFromLocalStorage } from './useLocalStorage';
-import { WEBSOCKET_URL, MAX_DATA_POINTS } from '@/lib/constants';
+import { WEBSOCKET_URL, MAX_DATA_POINTS, FEED_PROTOCOL_VERSION } from '@/lib/constants';
+import { getSessionToken } from '@/lib/session';
 
 export function useWebSocket() {
   const [status, setStatus] = useState<string>("Connecting...");
@@ -14,73 +15,114 @@ export function useWebSocket() {
   useLocalStorage('cab_chartData', chartData);
 
   useEffect(() => {
-    const ws = new WebSocket(WEBSOCKET_URL);
+    let socket: WebSocket | null = null;
+    let cancelled = false;
 
-    ws.onopen = () => {
-      setStatus("Connected");
-      console.log("WebSocket connected");
-    };
+    const connect = (token: string | null) => {
+      // browsers cannot set headers on a WebSocket, so the token goes in the query
+      const ws = new WebSocket(token ? `${WEBSOCKET_URL}?access_token=${encodeURIComponent(token)}` : WEBSOCKET_URL);
+
+      ws.onopen = () => {
+        setStatus("Connected");
+        console.log("WebSocket connected");
+      };
+
+      ws.onmessage = (event) => {
+        try {
+          const envelope: FeedEnvelope = JSON.parse(event.data);
+          if (envelope.version !== FEED_PROTOCOL_VERSION) {
+            console.warn("Unsupported feed protocol version:", envelope.version);
+            return;
+          }
+
+          if (envelope.type === 'snapshot') {
+            const snapshot = envelope.payload as FeedSnapshot;
+            setCurrentData((prev) => {
+              const next = { ...prev };
+              snapshot.results.forEach((result) => { next[result.symbol] = result; });
+              return next;
+            });
+            return;
+          }
+          if (envelope.type === 'error') {
+            console.error("Feed error:", envelope.payload);
+            return;
+          }
+          // opportunities, detections, status, alerts and heartbeats are not charted; only cash-futures quotes are
+          if (envelope.type !== 'quote') return;
+          const parsed = envelope.payload as ArbitrageData;
+
+          setCurrentData((prev) => ({
+            ...prev,
+            [parsed.symbol]: parsed,
+          }));
+
+          const timestamp = Date.now();
+          const timeString = new Date().toLocaleTimeString();
 
-    ws.onmessage = (event) => {
-      try {
-        const parsed: ArbitrageData = JSON.parse(event.data);
-
-        setCurrentData((prev) => ({
-          ...prev,
-          [parsed.symbol]: parsed,
-        }));
-
-        const timestamp = Date.now();
-        const timeString = new Date().toLocaleTimeString();
-
-        setChartData((prev) => {
-          const stockData = prev[parsed.symbol] || [];
-          const lastPoint = stockData[stockData.length - 1];
-          const baseVolatility = 0.003 + (Math.random() * 0.005);
-          const trend = (Math.random() - 0.5) * 2;
-          const open = lastPoint?.close || parsed.spot_price;
-          const priceChange = open * baseVolatility * trend;
-          const close = open + priceChange;
-          const upperWick = Math.abs(Math.random() * baseVolatility * open);
-          const lowerWick = Math.abs(Math.random() * baseVolatility * open);
+          setChartData((prev) => {
+            const stockData = prev[parsed.symbol] || [];
+            const lastPoint = stockData[stockData.length - 1];
+            const baseVolatility = 0.003 + (Math.random() * 0.005);
+            const trend = (Math.random() - 0.5) * 2;
+            const open = lastPoint?.close || parsed.spot_price;
+            const priceChange = open * baseVolatility * trend;
+            const close = open + priceChange;
+            const upperWick = Math.abs(Math.random() * baseVolatility * open);
+            const lowerWick = Math.abs(Math.random() * baseVolatility * open);
         
-          const high = Math.max(open, close) + upperWick;
-          const low = Math.min(open, close) - lowerWick;
+            const high = Math.max(open, close) + upperWick;
+            const low = Math.min(open, close) - lowerWick;
           
-          const newPoint: ChartDataPoint = {
-            time: timeString,
-            timestamp: timestamp,
-            spot: parsed.spot_price,
-            futures: parsed.futures_price,
-            spread: parsed.spread_percentage,
-            open: open,
-            high: high,
-            low: low,
-            close: close,
-          };
-
-          const updated = [...stockData, newPoint];
-          return {
-            ...prev,
-            [parsed.symbol]: updated.length > MAX_DATA_POINTS ? updated.slice(-MAX_DATA_POINTS) : updated,
-          };
-        });
-      } catch (e) {
-        console.error("Error parsing message:", e);
-      }
-    };
+            const newPoint: ChartDataPoint = {
+              time: timeString,
+              timestamp: timestamp,
+              spot: parsed.spot_price,
+              futures: parsed.futures_price,
+              spread: parsed.spread_percentage,
+              open: open,
+              high: high,
+              low: low,
+              close: close,
+            };
 
-    ws.onerror = (error) => {
-      console.error("WebSocket error:", error);
-      setStatus("Error");
-    };
+            const updated = [...stockData, newPoint];
+            return {
+              ...prev,
+              [parsed.symbol]: updated.length > MAX_DATA_POINTS ? updated.slice(-MAX_DATA_POINTS) : updated,
+            };
+          });
+        } catch (e) {
+          console.error("Error parsing message:", e);
+        }
+      };
+
+      ws.onerror = (error) => {
+        console.error("WebSocket error:", error);
+        setStatus("Error");
+      };
 
-    ws.onclose = () => {
-      setStatus("Disconnected");
-      console.log("WebSocket disconnected");
+      ws.onclose = () => {
+        setStatus("Disconnected");
+        console.log("WebSocket disconnected");
+      };
+
+      return ws;
     };
 
-    return () => ws.close();
+    getSessionToken()
+      .then((token) => {
+        if (!cancelled) socket = connect(token);
+      })
+      .catch((error) => {
+        console.error("Could not open a feed session:", error);
+        setStatus("Error");
+      });
+
+    return () => {
+      cancelled = true;
+      socket?.close();
+    };
   }, []);
 
   return { status, currentData, chartData, setChartData, setCurrentData };
@@ -23,4 +23,7 @@ export const TIMEFRAME_INTERVALS = {
   '1h': 3600000,
 };
 
-export const WEBSOCKET_URL = "ws://127.0.0.1:3030/ws";
\ No newline at end of file
+export const WEBSOCKET_URL = "ws://127.0.0.1:3030/ws";
+// served by app/api/session, which keeps the engine's API key off the client
+export const SESSION_ENDPOINT = "/api/session";
+export const FEED_PROTOCOL_VERSION = 1;
@@ -0,0 +1,22 @@
+import { SESSION_ENDPOINT } from './constants';
+
+interface Session {
+  token: string | null;
+  expires_at: number | null;
+}
+
+let cached: Session | null = null;
+
+// A read-only engine token from this app's server, reused until a minute before it
+// expires. Resolves to null when the engine runs without authentication.
+export async function getSessionToken(): Promise<string | null> {
+  const now = Date.now() / 1000;
+  if (cached && (cached.expires_at === null || cached.expires_at - 60 > now)) {
+    return cached.token;
+  }
+  const res = await fetch(SESSION_ENDPOINT, { method: 'POST', cache: 'no-store' });
+  if (!res.ok) throw new Error(`Session request failed: ${res.status}`);
+  const session: Session = await res.json();
+  cached = session;
+  return session.token;
+}
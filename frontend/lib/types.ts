@@ -15,6 +15,23 @@ export interface ArbitrageData {
   last_update: string;
 }
 
+// Every /ws frame is wrapped in a versioned envelope; `payload` depends on `type`
+export type FeedMessageType = 'quote' | 'opportunity' | 'detection' | 'status' | 'alert' | 'heartbeat' | 'error' | 'ack' | 'snapshot';
+
+export interface FeedEnvelope<T = unknown> {
+  type: FeedMessageType;
+  version: number;
+  seq: number;
+  ts: number;
+  payload: T;
+}
+
+export interface FeedSnapshot {
+  reason: 'connect' | 'resync';
+  missed: number;
+  results: ArbitrageData[];
+}
+
 export interface ChartDataPoint {
   time: string;
   timestamp: number;